@@ -12,70 +12,37 @@ use font_kit::{
 use material_icons::Icon;
 use serde_json::Value;
 use std::hash::{Hash, Hasher};
-use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{collections::BTreeMap, io::Read, sync::mpsc, thread};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    sync::{mpsc, Arc},
+    thread,
+};
 
-use ureq::{OrAnyStatus, Response, Transport};
 use uuid::Uuid;
 
-use crate::{egui_dock_style, syntax_highlighting, uri, Command, ReUi};
-pub type Result<T> = std::result::Result<T, Transport>;
-
-#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
-#[serde(default)]
-struct Resource {
-    /// HTTP response
-    url: String,
-    body: String,
-    headers: Vec<(String, String)>,
-    length: usize,
-    content_type: String,
-    status: usize,
-    status_text: String,
-    elapsed: u128,
-    // If set, the response was text with some supported syntax highlighting (e.g. ".rs" or ".md").
-    // colored_text: Option<ColoredText>,
-}
-
-impl Resource {
-    fn from_response(response: Result<Response>, elapsed: u128) -> Option<Self> {
-        if let Ok(response) = response {
-            let url = response.get_url().to_string();
-            let status = response.status().into();
-            let status_text = response.status_text().to_string();
-            let mut length = response
-                .header("Content-Length")
-                .unwrap_or_else(|| "0")
-                .parse()
-                .unwrap();
-            let content_type = response.content_type().to_string();
-
-            let mut headers = Vec::new();
-            for key in response.headers_names() {
-                headers.push((key.to_string(), response.header(&key).unwrap().to_string()));
-            }
-
-            let body = response.into_string().unwrap_or_default().to_string();
-            let body_len = body.len();
-            if length == 0 {
-                length = body_len;
-            }
-            return Some(Self {
-                url,
-                body,
-                headers,
-                length,
-                content_type,
-                status,
-                status_text,
-                elapsed,
-            });
-        } else {
-            return None;
-        }
-    }
-}
+use crate::environment::{unresolved_variables, Environment, VariableScope};
+use crate::executor::{
+    self, CancelToken, Failure, FailureKind, Outcome, PreparedRequest, Progress, Redirect,
+    RequestBody, Resource,
+};
+use crate::toasts::{Toast, ToastKind, ToastOptions, Toasts};
+use crate::{egui_dock_style, syntax_highlighting, toggle_switch, Command, ReUi};
+use crate::assertion::{self, Assertion, AssertionResult, Operator, Target};
+use crate::auth::{ApiKeyIn, Auth, AuthKind};
+use crate::cookies::{Cookie, CookieJar};
+use crate::diff::{self, BodyDiff, Change};
+use crate::history::{self, History, HistoryEntry, StatusFilter};
+use crate::oauth::{self, GrantType, OAuth2Config, Token, TokenCache};
+use crate::proxy::{ProxyConfig, ProxyMode};
+use crate::key_value::{self, KeyValue, Row};
+use crate::query::{self, Param};
+use crate::runner::{Run, RunnerConfig, StepState};
+use crate::script::{self, LogLevel, LogLine, Script, ScriptOutput, ScriptRequest};
+use crate::tls::{CertFormat, ClientCert, TlsInfo, TlsOptions, TlsSettings};
+use crate::timing::Timings;
+use crate::{hex, multipart};
 
 #[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -116,13 +83,15 @@ impl Method {
         } else if method.to_uppercase() == "POST" {
             return Method::Post;
         } else if method.to_uppercase() == "PUT" {
-            return Method::Post;
+            return Method::Put;
         } else if method.to_uppercase() == "PATCH" {
             return Method::Patch;
         } else if method.to_uppercase() == "DELETE" {
             return Method::Delete;
         } else if method.to_uppercase() == "HEAD" {
             return Method::Head;
+        } else if method.to_uppercase() == "OPTIONS" {
+            return Method::Options;
         } else {
             return Method::Get;
         }
@@ -135,6 +104,10 @@ enum ContentType {
     Json,
     FormUrlEncoded,
     FormData,
+    /// Free text sent as is, see [`RawType`].
+    Raw,
+    /// A file streamed from disk.
+    Binary,
 }
 
 impl Default for ContentType {
@@ -143,12 +116,103 @@ impl Default for ContentType {
     }
 }
 
+/// Content type of a [`ContentType::Raw`] body.
+#[derive(Clone, Copy, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+enum RawType {
+    #[default]
+    Text,
+    Xml,
+    Html,
+    JavaScript,
+    /// Uses `Location.custom_content_type`.
+    Custom,
+}
+
+impl RawType {
+    fn mime(self) -> &'static str {
+        match self {
+            RawType::Text => "text/plain",
+            RawType::Xml => "application/xml",
+            RawType::Html => "text/html",
+            RawType::JavaScript => "application/javascript",
+            RawType::Custom => "",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+enum FormPartKind {
+    #[default]
+    Text,
+    File,
+}
+
+/// A row of a `multipart/form-data` body.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct FormPart {
+    enabled: bool,
+    key: String,
+    /// The text, or the path of the file to upload.
+    value: String,
+    kind: FormPartKind,
+    /// Guessed from the file name (or omitted for text) when empty.
+    content_type: String,
+    description: String,
+}
+
+impl Default for FormPart {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            key: Default::default(),
+            value: Default::default(),
+            kind: Default::default(),
+            content_type: Default::default(),
+            description: Default::default(),
+        }
+    }
+}
+
+/// Bulk edited as `key: value`, kept file parts stay files.
+impl Row for FormPart {
+    fn line(&self) -> (bool, &str, &str) {
+        (self.enabled, &self.key, &self.value)
+    }
+
+    fn columns(&mut self) -> (&mut bool, &mut String, &mut String, &mut String) {
+        (
+            &mut self.enabled,
+            &mut self.key,
+            &mut self.value,
+            &mut self.description,
+        )
+    }
+}
+
+impl FormPart {
+    fn to_part(&self) -> multipart::Part {
+        multipart::Part {
+            name: self.key.clone(),
+            body: match self.kind {
+                FormPartKind::Text => multipart::PartBody::Text(self.value.clone()),
+                FormPartKind::File => multipart::PartBody::File(self.value.clone()),
+            },
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 enum RequestEditor {
     Params,
     Body,
     Headers,
+    Auth,
+    Tests,
+    Scripts,
+    Settings,
 }
 
 impl Default for RequestEditor {
@@ -162,31 +226,242 @@ impl Default for RequestEditor {
 struct ApiCollection {
     name: String,
     buffers: BTreeMap<String, Location>,
+    /// Variables shared by every request of the collection.
+    variables: Vec<(String, String)>,
+    proxy: ProxyConfig,
+    /// Added to the global TLS settings, its client certificates are tried first.
+    tls: TlsSettings,
+    /// Cookies set by responses, sent with later requests.
+    cookies: CookieJar,
+    /// OAuth 2.0 tokens, refreshed when they expire.
+    oauth_tokens: TokenCache,
 }
 
 #[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[serde(default)]
 struct Location {
     id: String,
     name: String,
     url: String,
     method: Method,
-    params: Vec<(String, String)>,
+    /// The source of truth for the query of `url`, see [`query`].
+    params: Vec<Param>,
     body: String,
-    form_params: Vec<(String, String)>,
-    header: Vec<(String, String)>,
+    form_params: Vec<KeyValue>,
+    form_data: Vec<FormPart>,
+    header: Vec<KeyValue>,
     content_type: ContentType,
+    raw_type: RawType,
+    custom_content_type: String,
+    /// Path of the file sent as the [`ContentType::Binary`] body.
+    binary_file: String,
     response: Option<Resource>,
+    /// Set instead of `response` when the last request failed.
+    failure: Option<Failure>,
+    /// Overrides [`Settings::follow_redirects`] if set.
+    follow_redirects: Option<bool>,
+    /// Overrides [`Settings::max_redirects`] if set.
+    max_redirects: Option<usize>,
+    auth: Auth,
+    /// Run before the request is resolved and sent, see [`script`].
+    pre_request_script: String,
+    /// Run after a response was received.
+    test_script: String,
+    /// Checked after every response, see [`assertion`].
+    assertions: Vec<Assertion>,
+    /// The results of `assertions` for `response`.
+    assertion_results: Vec<AssertionResult>,
+}
+
+impl From<&Location> for PreparedRequest {
+    fn from(location: &Location) -> Self {
+        let mut request = PreparedRequest {
+            method: location.method.to_text(),
+            url: query::with_params(&location.url, &location.params),
+            headers: key_value::enabled_pairs(&location.header),
+            ..Default::default()
+        };
+        if matches!(
+            location.method,
+            Method::Post | Method::Put | Method::Patch | Method::Delete
+        ) {
+            match location.content_type {
+                ContentType::Json | ContentType::Raw => {
+                    request.body = RequestBody::Text {
+                        content_type: location.body_content_type(),
+                        text: location.body.clone(),
+                    };
+                }
+                ContentType::Binary => {
+                    request.body = RequestBody::File {
+                        content_type: multipart::guess_content_type(&location.binary_file)
+                            .to_owned(),
+                        path: location.binary_file.clone(),
+                    };
+                }
+                ContentType::FormUrlEncoded => {
+                    request.body =
+                        RequestBody::Form(key_value::enabled_pairs(&location.form_params));
+                }
+                ContentType::FormData => {
+                    request.body = RequestBody::Multipart(
+                        location
+                            .form_data
+                            .iter()
+                            .filter(|p| p.enabled && !p.key.is_empty())
+                            .map(FormPart::to_part)
+                            .collect(),
+                    );
+                }
+            }
+        }
+        request
+    }
+}
+
+/// `rows` with every `{{variable}}` in their keys and values replaced.
+fn resolve_rows(scope: &VariableScope, rows: &[KeyValue]) -> Vec<KeyValue> {
+    rows.iter()
+        .map(|row| KeyValue {
+            key: scope.resolve(&row.key),
+            value: scope.resolve(&row.value),
+            ..row.clone()
+        })
+        .collect()
+}
+
+impl Location {
+    /// The `Content-Type` sent with `body` for JSON and raw bodies.
+    ///
+    /// A blank custom type falls back to `text/plain`.
+    fn body_content_type(&self) -> String {
+        match (self.content_type, self.raw_type) {
+            (ContentType::Raw, RawType::Custom) if !self.custom_content_type.trim().is_empty() => {
+                self.custom_content_type.trim().to_owned()
+            }
+            (ContentType::Raw, RawType::Custom) => RawType::Text.mime().to_owned(),
+            (ContentType::Raw, raw_type) => raw_type.mime().to_owned(),
+            _ => "application/json".to_owned(),
+        }
+    }
+
+    /// The request as seen by scripts, before `{{variables}}` are resolved.
+    fn script_request(&self) -> ScriptRequest {
+        ScriptRequest {
+            method: self.method.to_text(),
+            url: query::with_params(&self.url, &self.params),
+            headers: key_value::enabled_pairs(&self.header),
+            body: self.body.clone(),
+            form: match self.content_type {
+                ContentType::FormUrlEncoded => key_value::enabled_pairs(&self.form_params),
+                ContentType::FormData => self
+                    .form_data
+                    .iter()
+                    .filter(|p| p.enabled && p.kind == FormPartKind::Text)
+                    .map(|p| (p.key.clone(), p.value.clone()))
+                    .collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    /// Apply the changes a pre-request script made to `request`.
+    fn apply_script_request(&mut self, request: ScriptRequest) {
+        let original = self.script_request();
+        let form_changed = request.form != original.form;
+        self.method = Method::from_text(request.method);
+        self.params = query::sync(&request.url, &self.params);
+        self.url = request.url;
+        if request.headers != original.headers {
+            self.header.retain(|row| !row.enabled);
+            self.header.extend(
+                request
+                    .headers
+                    .into_iter()
+                    .map(|(key, value)| KeyValue::new(key, value)),
+            );
+        }
+        self.body = request.body;
+        let form = request.form.into_iter();
+        match self.content_type {
+            _ if !form_changed => {}
+            ContentType::FormUrlEncoded => {
+                self.form_params.retain(|row| !row.enabled);
+                self.form_params
+                    .extend(form.map(|(key, value)| KeyValue::new(key, value)));
+            }
+            ContentType::FormData => {
+                // Files are not seen by scripts.
+                self.form_data
+                    .retain(|p| !p.enabled || p.kind == FormPartKind::File);
+                self.form_data.extend(form.map(|(key, value)| FormPart {
+                    key,
+                    value,
+                    ..Default::default()
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    /// A copy of this location with every `{{variable}}` replaced by its value.
+    fn resolved(&self, scope: &VariableScope) -> Location {
+        Location {
+            url: scope.resolve(&self.url),
+            params: self
+                .params
+                .iter()
+                .map(|p| Param {
+                    key: scope.resolve(&p.key),
+                    value: scope.resolve(&p.value),
+                    ..p.clone()
+                })
+                .collect(),
+            body: scope.resolve(&self.body),
+            form_params: resolve_rows(scope, &self.form_params),
+            form_data: self
+                .form_data
+                .iter()
+                .map(|p| FormPart {
+                    key: scope.resolve(&p.key),
+                    value: scope.resolve(&p.value),
+                    content_type: scope.resolve(&p.content_type),
+                    ..p.clone()
+                })
+                .collect(),
+            header: resolve_rows(scope, &self.header),
+            custom_content_type: scope.resolve(&self.custom_content_type),
+            binary_file: scope.resolve(&self.binary_file),
+            auth: self.auth.resolved(|s| scope.resolve(s)),
+            assertions: self
+                .assertions
+                .iter()
+                .map(|a| a.resolved(|s| scope.resolve(s)))
+                .collect(),
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[serde(default)]
 struct Directory {
     id: String,
     name: String,
     parent: String,
     leaf: bool,
     locations: Vec<String>,
+    /// Variables shared by the requests of this directory.
+    variables: Vec<(String, String)>,
+    proxy: ProxyConfig,
+    /// Inherited by the requests of this directory.
+    auth: Auth,
+    /// Run before the pre-request script of each request of this directory.
+    pre_request_script: String,
+    /// Run before the test script of each request of this directory.
+    test_script: String,
 }
 
 #[derive(Default, serde::Serialize, serde::Deserialize)]
@@ -259,17 +534,537 @@ impl Hash for Color {
     }
 }
 
+/// App wide preferences, edited in the settings window.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct Settings {
+    /// Response body size kept in memory, larger bodies are spilled to a temp file.
+    body_limit_mb: usize,
+    follow_redirects: bool,
+    max_redirects: usize,
+    /// Inheriting means the system proxy from the environment variables.
+    proxy: ProxyConfig,
+    tls: TlsSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            body_limit_mb: executor::DEFAULT_BODY_LIMIT / (1024 * 1024),
+            follow_redirects: true,
+            max_redirects: executor::DEFAULT_MAX_REDIRECTS,
+            proxy: ProxyConfig::default(),
+            tls: TlsSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The request to send for a resolved `location`, with these settings applied.
+    ///
+    /// `proxy` is the effective proxy config of the location, see [`proxy_config`],
+    /// `collection_tls` the TLS settings of its collection.
+    fn prepare(
+        &self,
+        location: &Location,
+        proxy: &ProxyConfig,
+        collection_tls: &TlsSettings,
+    ) -> PreparedRequest {
+        PreparedRequest {
+            proxy: proxy.proxy_for(&location.url, |name| std::env::var(name).ok()),
+            tls: TlsOptions::resolve(&location.url, [collection_tls, &self.tls]),
+            follow_redirects: location.follow_redirects.unwrap_or(self.follow_redirects),
+            max_redirects: location.max_redirects.unwrap_or(self.max_redirects),
+            body_limit: self.body_limit_mb * 1024 * 1024,
+            ..PreparedRequest::from(location)
+        }
+    }
+}
+
+fn ui_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+        ui.label("Follow redirects");
+        ui.checkbox(&mut settings.follow_redirects, "");
+        ui.end_row();
+        ui.label("Max redirects");
+        ui.add(egui::DragValue::new(&mut settings.max_redirects).clamp_range(0..=100));
+        ui.end_row();
+        ui.label("Response body in memory");
+        ui.add(
+            egui::DragValue::new(&mut settings.body_limit_mb)
+                .clamp_range(1..=1024)
+                .suffix(" MB"),
+        )
+        .on_hover_text("Larger responses are saved to a temp file and shown page by page");
+        ui.end_row();
+    });
+    ui.separator();
+    ui.label("Proxy");
+    ui_proxy(ui, "settings_proxy", &mut settings.proxy, "system");
+    ui.separator();
+    ui.label("TLS");
+    ui_tls(ui, "settings_tls", &mut settings.tls);
+}
+
+/// Edit TLS settings: trusted CA files, skipping verification and client certificates.
+fn ui_tls(ui: &mut egui::Ui, id_source: &str, tls: &mut TlsSettings) {
+    ui.push_id(id_source, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("CA certificates");
+            if ui.button("add").clicked() {
+                tls.ca_files.push(String::new());
+            }
+        });
+        let mut del = None;
+        for (i, file) in tls.ca_files.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui_file_path(ui, file, "PEM file with trusted CA certificates");
+                    if ui.button("del").clicked() {
+                        del = Some(i);
+                    }
+                });
+            });
+        }
+        if let Some(i) = del {
+            tls.ca_files.remove(i);
+        }
+
+        ui.checkbox(&mut tls.insecure, "Skip certificate verification");
+        if tls.insecure {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ Any server certificate is accepted, connections can be intercepted",
+            );
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Client certificates");
+            if ui.button("add").clicked() {
+                tls.client_certs.push(ClientCert::default());
+            }
+        });
+        let mut del = None;
+        for (i, cert) in tls.client_certs.iter_mut().enumerate() {
+            ui.push_id(("client_cert", i), |ui| {
+                egui::Grid::new("client_cert")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Host");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut cert.host)
+                                    .hint_text("any host, or *.example.org"),
+                            );
+                            if ui.button("del").clicked() {
+                                del = Some(i);
+                            }
+                        });
+                        ui.end_row();
+                        ui.label("Format");
+                        egui::ComboBox::from_id_source("format")
+                            .selected_text(cert.format.text())
+                            .show_ui(ui, |ui| {
+                                for format in [CertFormat::Pem, CertFormat::Pkcs12] {
+                                    ui.selectable_value(&mut cert.format, format, format.text());
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Certificate");
+                        ui.horizontal(|ui| {
+                            let hint = match cert.format {
+                                CertFormat::Pem => "certificate chain PEM file",
+                                CertFormat::Pkcs12 => ".p12 or .pfx file",
+                            };
+                            ui_file_path(ui, &mut cert.cert_file, hint);
+                        });
+                        ui.end_row();
+                        match cert.format {
+                            CertFormat::Pem => {
+                                ui.label("Key");
+                                ui.horizontal(|ui| {
+                                    ui_file_path(
+                                        ui,
+                                        &mut cert.key_file,
+                                        "key PEM file, if not in the certificate file",
+                                    );
+                                });
+                            }
+                            CertFormat::Pkcs12 => {
+                                ui.label("Password");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut cert.password).password(true),
+                                );
+                            }
+                        }
+                        ui.end_row();
+                    });
+                ui.separator();
+            });
+        }
+        if let Some(i) = del {
+            tls.client_certs.remove(i);
+        }
+    });
+}
+
+/// A file path with a button to pick it.
+fn ui_file_path(ui: &mut egui::Ui, path: &mut String, hint: &str) {
+    ui.add(egui::TextEdit::singleline(path).hint_text(hint));
+    if ui.button("...").clicked() {
+        if let Some(picked) = rfd::FileDialog::new().pick_file() {
+            *path = picked.display().to_string();
+        }
+    }
+}
+
+/// Edit a proxy config, `inherit` names what inheriting resolves to.
+fn ui_proxy(ui: &mut egui::Ui, id_source: &str, proxy: &mut ProxyConfig, inherit: &str) {
+    let text = |mode: ProxyMode| match mode {
+        ProxyMode::Inherit => format!("inherit ({inherit})"),
+        mode => mode.text().to_owned(),
+    };
+    egui::Grid::new(id_source).num_columns(2).show(ui, |ui| {
+        ui.label("Mode");
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(text(proxy.mode))
+            .show_ui(ui, |ui| {
+                for mode in [
+                    ProxyMode::Inherit,
+                    ProxyMode::Direct,
+                    ProxyMode::System,
+                    ProxyMode::Manual,
+                ] {
+                    ui.selectable_value(&mut proxy.mode, mode, text(mode));
+                }
+            });
+        ui.end_row();
+        if proxy.mode != ProxyMode::Manual {
+            return;
+        }
+        ui.label("Url");
+        ui.add(egui::TextEdit::singleline(&mut proxy.url).hint_text("http://proxy:3128"))
+            .on_hover_text("http://, socks4://, socks4a:// or socks5://");
+        ui.end_row();
+        ui.label("Username");
+        ui.text_edit_singleline(&mut proxy.username);
+        ui.end_row();
+        ui.label("Password");
+        ui.add(egui::TextEdit::singleline(&mut proxy.password).password(true));
+        ui.end_row();
+        ui.label("No proxy for");
+        ui.add(
+            egui::TextEdit::singleline(&mut proxy.no_proxy).hint_text("localhost, .internal.corp"),
+        );
+        ui.end_row();
+    });
+}
+
+/// State of the request sent from a tab, keyed by `Location.id`.
+#[derive(Clone)]
 enum RunState {
-    Running,
+    Running {
+        cancel: CancelToken,
+        started: Instant,
+        progress: Progress,
+        /// Name of the active environment.
+        environment: Option<String>,
+    },
+}
+
+/// A request finished on its worker thread, see [`Outgoing::send`].
+struct Finished {
+    /// The `Location.id` of the tab.
+    id: String,
+    cancel: CancelToken,
+    /// The request as sent, with the effective auth, recorded in the history.
+    sent: Location,
+    outcome: Outcome,
+    /// The OAuth 2.0 token requested for it.
+    token: Option<(OAuth2Config, Token)>,
+    /// Output of the pre-request scripts, without the error that became the failure.
+    pre_request: Option<ScriptOutput>,
+    /// Output of the test scripts, which only run for a response.
+    tests: Option<ScriptOutput>,
+}
+
+/// Abort the request running for `location`, recording a cancelled outcome right away.
+///
+/// The worker thread notices the cancellation and stops waiting for the response,
+/// its late result is then ignored by [`HttpApp::receive_outcomes`].
+fn cancel_request(run_state: &mut BTreeMap<String, RunState>, location: &mut Location) {
+    if let Some(RunState::Running {
+        cancel, started, ..
+    }) = run_state.remove(&location.id)
+    {
+        cancel.cancel();
+        if let Some(resource) = location.response.take() {
+            resource.remove_spill_file();
+        }
+        location.failure = Some(Failure::cancelled(started));
+    }
+}
+
+/// The pre-request or test scripts of `location` and its `directories`, in the
+/// order they run.
+fn scripts(directories: &[&Directory], location: &Location, tests: bool) -> Vec<Script> {
+    let kind = if tests { "tests" } else { "pre-request" };
+    let code = |pre_request: &String, test: &String| {
+        if tests {
+            test.clone()
+        } else {
+            pre_request.clone()
+        }
+    };
+    directories
+        .iter()
+        .map(|d| (d.name.as_str(), code(&d.pre_request_script, &d.test_script)))
+        .chain([(
+            location.name.as_str(),
+            code(&location.pre_request_script, &location.test_script),
+        )])
+        .filter(|(_, code)| !code.trim().is_empty())
+        .map(|(name, code)| (format!("{name}: {kind}"), code))
+        .collect()
+}
+
+/// The directory containing `location_id` and its parents, from the outermost
+/// to the innermost.
+fn directories_of<'a>(
+    directory: &'a BTreeMap<String, Directory>,
+    location_id: &str,
+) -> Vec<&'a Directory> {
+    let mut directories: Vec<&Directory> = directory
+        .values()
+        .find(|d| d.locations.iter().any(|l| l == location_id))
+        .into_iter()
+        .collect();
+    while let Some(parent) = directories.last().and_then(|d| directory.get(&d.parent)) {
+        // A parent that is already in the chain would loop forever.
+        if directories.iter().any(|d| d.id == parent.id) {
+            break;
+        }
+        directories.push(parent);
+    }
+    directories.reverse();
+    directories
+}
+
+/// Merge the variables visible to `location_id`, from the lowest to the highest precedence:
+/// globals, the collection, the directories containing the location from the
+/// outermost to the innermost and the active environment.
+fn variable_scope(
+    globals: &[(String, String)],
+    api_collection: &ApiCollection,
+    directory: &BTreeMap<String, Directory>,
+    environment: Option<&Environment>,
+    location_id: &str,
+) -> VariableScope {
+    let mut scope = VariableScope::default();
+    scope.push_layer(globals);
+    scope.push_layer(&api_collection.variables);
+    for dir in directories_of(directory, location_id) {
+        scope.push_layer(&dir.variables);
+    }
+    if let Some(environment) = environment {
+        scope.push_layer(&environment.variables);
+    }
+    scope
+}
+
+/// The proxy config of `location_id`, the same scopes as [`variable_scope`] apply.
+fn proxy_config(
+    settings: &Settings,
+    api_collection: &ApiCollection,
+    directory: &BTreeMap<String, Directory>,
+    environment: Option<&Environment>,
+    location_id: &str,
+) -> ProxyConfig {
+    let directories = directories_of(directory, location_id);
+    ProxyConfig::effective(
+        environment
+            .map(|e| &e.proxy)
+            .into_iter()
+            .chain(directories.iter().rev().map(|d| &d.proxy))
+            .chain([&api_collection.proxy, &settings.proxy]),
+    )
+}
+
+/// A request of a tab with everything needed to send it, see [`Outgoing::send`].
+struct Outgoing {
+    /// The `Location.id` of the tab.
+    id: String,
+    /// The request as edited, before its scripts ran and its variables were resolved.
+    location: Location,
+    scope: VariableScope,
+    pre_request_scripts: Vec<Script>,
+    test_scripts: Vec<Script>,
+    settings: Settings,
+    proxy: ProxyConfig,
+    collection_tls: TlsSettings,
+    directory_auth: Auth,
+    cookies: CookieJar,
+    oauth_tokens: TokenCache,
+    /// Name of the active environment.
+    environment: Option<String>,
+}
+
+impl Outgoing {
+    /// Collect the scripts of the `location` of tab `id` and the proxy, TLS
+    /// settings, cookies and auth that apply to it.
+    fn new(
+        id: &str,
+        location: &Location,
+        scope: VariableScope,
+        settings: &Settings,
+        api_collection: &ApiCollection,
+        directory: &BTreeMap<String, Directory>,
+        environment: Option<&Environment>,
+    ) -> Self {
+        let directories = directories_of(directory, id);
+        Outgoing {
+            id: id.to_owned(),
+            location: location.clone(),
+            scope,
+            pre_request_scripts: scripts(&directories, location, false),
+            test_scripts: scripts(&directories, location, true),
+            settings: settings.clone(),
+            proxy: proxy_config(settings, api_collection, directory, environment, id),
+            collection_tls: api_collection.tls.clone(),
+            directory_auth: Auth::effective(directories.iter().rev().map(|d| &d.auth)),
+            cookies: api_collection.cookies.clone(),
+            oauth_tokens: api_collection.oauth_tokens.clone(),
+            environment: environment.map(|e| e.name.clone()),
+        }
+    }
+
+    /// Send the request on a worker thread, the outcome is received by
+    /// [`HttpApp::receive_outcomes`].
+    fn send(
+        self,
+        ctx: &egui::Context,
+        sender: &mpsc::Sender<Finished>,
+        run_state: &mut BTreeMap<String, RunState>,
+    ) {
+        let cancel = CancelToken::default();
+        let progress = Progress::default();
+        run_state.insert(
+            self.id.clone(),
+            RunState::Running {
+                cancel: cancel.clone(),
+                started: Instant::now(),
+                progress: progress.clone(),
+                environment: self.environment.clone(),
+            },
+        );
+        let sender = sender.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            sender.send(self.run(&ctx, cancel, &progress)).ok();
+            ctx.request_repaint();
+        });
+    }
+
+    /// Run the pre-request scripts, resolve the request with the variables
+    /// they set, send it and run the test scripts on the response.
+    ///
+    /// If a pre-request script fails nothing is sent, its error is the failure.
+    fn run(self, ctx: &egui::Context, cancel: CancelToken, progress: &Progress) -> Finished {
+        let mut scope = self.scope;
+        let mut scripted = self.location;
+        let mut pre_request = None;
+        if !self.pre_request_scripts.is_empty() {
+            let mut request = scripted.script_request();
+            let mut output = script::pre_request(&self.pre_request_scripts, &mut request, &scope);
+            scope.push_layer(&output.variables);
+            if let Some(error) = output.error.take() {
+                return Finished {
+                    id: self.id,
+                    cancel,
+                    sent: scripted,
+                    outcome: Outcome::Failure(Failure {
+                        kind: FailureKind::Script,
+                        message: error,
+                        ..Default::default()
+                    }),
+                    token: None,
+                    pre_request: Some(output),
+                    tests: None,
+                };
+            }
+            scripted.apply_script_request(request);
+            pre_request = Some(output);
+        }
+
+        let resolved = scripted.resolved(&scope);
+        let mut request = PreparedRequest {
+            cookies: Some(self.cookies),
+            ..self
+                .settings
+                .prepare(&resolved, &self.proxy, &self.collection_tls)
+        };
+        let auth =
+            Auth::effective([&resolved.auth, &self.directory_auth]).resolved(|s| scope.resolve(s));
+        auth.apply(&mut request);
+        let mut token = None;
+        let authorized = if auth.kind == AuthKind::OAuth2 {
+            let open_url = |url: &str| {
+                ctx.open_url(egui::OpenUrl::new_tab(url));
+                ctx.request_repaint();
+            };
+            let cached = self.oauth_tokens.get(&auth.oauth2);
+            oauth::authorize(&mut request, &auth.oauth2, cached, &cancel, &open_url)
+                .map(|fresh| token = fresh.map(|t| (auth.oauth2.clone(), t)))
+        } else {
+            Ok(())
+        };
+        let outcome = match authorized {
+            Ok(()) => executor::execute(&request, &cancel, progress),
+            Err(failure) => Outcome::Failure(failure),
+        };
+
+        let sent = Location {
+            auth,
+            response: None,
+            failure: None,
+            ..resolved
+        };
+        let tests = match &outcome {
+            Outcome::Response(resource) if !self.test_scripts.is_empty() => {
+                let request = sent.script_request();
+                Some(script::tests(
+                    &self.test_scripts,
+                    &request,
+                    resource,
+                    &scope,
+                ))
+            }
+            _ => None,
+        };
+        Finished {
+            id: self.id,
+            cancel,
+            sent,
+            outcome,
+            token,
+            pre_request,
+            tests,
+        }
+    }
 }
 
 struct MyContext<'a> {
     api_collection: &'a mut ApiCollection,
+    directory: &'a BTreeMap<String, Directory>,
+    globals: &'a Vec<(String, String)>,
+    environment: Option<&'a Environment>,
+    settings: &'a Settings,
     reqest_editor: &'a mut RequestEditor,
-    sender: &'a mpsc::Sender<Resource>,
-    receiver: &'a mpsc::Receiver<Resource>,
+    sender: &'a mpsc::Sender<Finished>,
     added_nodes: &'a mut Vec<Location>,
-    run_state: &'a mut Vec<RunState>,
+    run_state: &'a mut BTreeMap<String, RunState>,
+    /// Tabs restored from the history to send right away.
+    pending_sends: &'a mut Vec<String>,
 }
 
 impl TabViewer for MyContext<'_> {
@@ -279,221 +1074,84 @@ impl TabViewer for MyContext<'_> {
         Frame::none()
             .inner_margin(egui::Margin::same(10.0))
             .show(ui, |ui| {
-                let mut add_location = false;
+                let scope = variable_scope(
+                    self.globals,
+                    self.api_collection,
+                    self.directory,
+                    self.environment,
+                    tab,
+                );
+                let directory_auth = Auth::effective(
+                    directories_of(self.directory, tab)
+                        .iter()
+                        .rev()
+                        .map(|d| &d.auth),
+                );
                 let location = self.api_collection.buffers.get_mut(tab).unwrap();
 
-                let trigger_fetch = ui_url(ui, location);
-
-                if trigger_fetch {
-                    self.run_state.push(RunState::Running);
-
-                    let mut request = ureq::request(&location.method.to_text(), &location.url)
-                        .timeout(Duration::from_secs(10));
-
-                    let headers = location.header.iter().filter(|e| (e.0.is_empty() == false));
-                    for e in headers {
-                        request = request.set(&e.0, &e.1);
-                    }
-
-                    let sender = self.sender.clone();
-                    let resource_location = location.clone();
-                    let ctx = ui.ctx().clone();
-                    thread::spawn(move || {
-                        let start = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis();
-                        let response = match resource_location.method {
-                            Method::Get => {
-                                // let params = resource_location
-                                //     .params
-                                //     .iter()
-                                //     .filter(|e| (e.0.is_empty() == false));
-                                // for e in params {
-                                //     request = request.query(&e.0, &e.1);
-                                // }
-                                request.call().or_any_status()
-                            }
-                            Method::Post | Method::Put | Method::Patch | Method::Delete => {
-                                match resource_location.content_type {
-                                    ContentType::Json => request
-                                        .set("Content-Type", "application/json")
-                                        .send_string(&resource_location.body)
-                                        .or_any_status(),
-                                    ContentType::FormUrlEncoded => {
-                                        let params = resource_location
-                                            .params
-                                            .iter()
-                                            .filter(|e| (e.0.is_empty() == false));
-                                        for e in params {
-                                            request = request.query(&e.0, &e.1);
-                                        }
-                                        let from_param: Vec<(&str, &str)> = resource_location
-                                            .form_params
-                                            .as_slice()
-                                            .into_iter()
-                                            .map(|f| (f.0.as_str(), f.1.as_str()))
-                                            .collect();
-                                        request.send_form(&from_param[..]).or_any_status()
-                                    }
-                                    _ => request.call().or_any_status(),
-                                }
-                            }
-                            _ => request.call().or_any_status(),
-                        };
-                        let stop = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis();
-                        let resource = Resource::from_response(response, stop - start);
-                        if let Some(resource) = resource {
-                            sender.send(resource).unwrap();
-                            ctx.request_repaint();
-                        }
-                    });
+                let trigger_fetch = ui_url(ui, location, &scope, &directory_auth);
+                if self.settings.tls.insecure || self.api_collection.tls.insecure {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "⚠ TLS certificate verification is disabled",
+                    );
                 }
 
-                if self.run_state.len() > 0 {
-                    ui.spinner();
+                let resend = self.pending_sends.contains(tab);
+                self.pending_sends.retain(|id| id != tab);
+                if (trigger_fetch || resend) && !self.run_state.contains_key(tab) {
+                    Outgoing::new(
+                        tab,
+                        &self.api_collection.buffers[tab],
+                        scope.clone(),
+                        self.settings,
+                        self.api_collection,
+                        self.directory,
+                        self.environment,
+                    )
+                    .send(ui.ctx(), self.sender, self.run_state);
                 }
+                let ApiCollection {
+                    buffers,
+                    oauth_tokens,
+                    ..
+                } = &mut *self.api_collection;
+                let location = buffers.get_mut(tab).unwrap();
 
-                match self.receiver.try_recv() {
-                    Ok(resource) => {
-                        location.response = Some(resource);
-                        self.run_state.clear();
-                    }
-                    Err(_) => {}
+                if let Some(RunState::Running { progress, .. }) = self.run_state.get(tab) {
+                    let progress = progress.clone();
+                    ui.horizontal(|ui| {
+                        let ctx = ui.ctx().clone();
+                        let button = Command::CancelRequest.menu_button(&ctx);
+                        let cancel = ui
+                            .add(button)
+                            .on_hover_text(Command::CancelRequest.tooltip())
+                            .clicked();
+                        ui_progress(ui, &progress);
+                        if cancel {
+                            cancel_request(self.run_state, location);
+                        }
+                    });
+                    // The worker thread only wakes the UI once it is done.
+                    ui.ctx()
+                        .request_repaint_after(std::time::Duration::from_millis(100));
                 }
 
                 ui.horizontal(|ui| {
                     ui.selectable_value(self.reqest_editor, RequestEditor::Params, "Params");
                     ui.selectable_value(self.reqest_editor, RequestEditor::Body, "Body");
                     ui.selectable_value(self.reqest_editor, RequestEditor::Headers, "Headers");
+                    ui.selectable_value(self.reqest_editor, RequestEditor::Auth, "Auth");
+                    ui.selectable_value(self.reqest_editor, RequestEditor::Tests, "Tests");
+                    ui.selectable_value(self.reqest_editor, RequestEditor::Scripts, "Scripts");
+                    ui.selectable_value(self.reqest_editor, RequestEditor::Settings, "Settings");
                 });
 
                 match self.reqest_editor {
                     RequestEditor::Params => {
-                        ui.horizontal(|ui| {
-                            ui.label("Query Params");
-                            if ui.button("add").clicked() {
-                                add_location = true;
-                                location.params.push(("".to_owned(), "".to_owned()));
-                                ui.end_row();
-                            }
-                        });
-                        egui::Grid::new("query_params")
-                            .num_columns(3)
-                            .min_col_width(300.0)
-                            .spacing(egui::vec2(
-                                ui.spacing().item_spacing.x * 0.5,
-                                ui.spacing().item_spacing.x * 0.5,
-                            ))
-                            .show(ui, |ui| {
-                                // ui.horizontal(|ui| {
-                                if location.params.is_empty() {
-                                    location.params.push(("".to_owned(), "".to_owned()));
-                                    // });
-                                    ui.end_row();
-                                }
-
-                                let mut i = 0 as usize;
-                                while i < location.params.len() {
-                                    if ui.text_edit_singleline(&mut location.params[i].0).changed()
-                                    {
-                                        let urlstr = location.url.clone();
-                                        let url = uri(&urlstr);
-                                        if url.is_ok() {
-                                            let mut url = url.ok().unwrap().1;
-                                            if url.query.is_some() {
-                                                url.query.as_mut().unwrap().clear();
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            } else {
-                                                url.query = Some(vec![]);
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            }
-                                            location.url = url.to_string();
-                                        }
-                                    };
-                                    if ui.text_edit_singleline(&mut location.params[i].1).changed()
-                                    {
-                                        let urlstr = location.url.clone();
-                                        let url = uri(&urlstr);
-                                        if url.is_ok() {
-                                            let mut url = url.ok().unwrap().1;
-                                            if url.query.is_some() {
-                                                url.query.as_mut().unwrap().clear();
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            } else {
-                                                url.query = Some(vec![]);
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            }
-                                            location.url = url.to_string();
-                                        }
-                                    };
-                                    // ui.text_edit_singleline(&mut location.params[i].0);
-                                    // ui.text_edit_singleline(&mut location.params[i].1);
-                                    if ui.button("del").clicked() {
-                                        location.params.remove(i);
-                                        let urlstr = location.url.clone();
-                                        let url = uri(&urlstr);
-                                        if url.is_ok() {
-                                            let mut url = url.ok().unwrap().1;
-                                            if url.query.is_some() {
-                                                url.query.as_mut().unwrap().clear();
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            } else {
-                                                url.query = Some(vec![]);
-                                                location.params.iter().for_each(|p| {
-                                                    if p.0.len() > 0 {
-                                                        url.query
-                                                            .as_mut()
-                                                            .unwrap()
-                                                            .push((&p.0, &p.1));
-                                                    }
-                                                });
-                                            }
-                                            location.url = url.to_string();
-                                        }
-                                    }
-                                    i = i + 1;
-                                    ui.end_row();
-                                }
-                            });
+                        if ui_key_values(ui, "query_params", "Query Params", &mut location.params) {
+                            location.url = query::with_params(&location.url, &location.params);
+                        }
                     }
                     RequestEditor::Body => {
                         ui.horizontal(|ui| {
@@ -512,8 +1170,68 @@ impl TabViewer for MyContext<'_> {
                                 ContentType::FormUrlEncoded,
                                 "x-www-form-url-encoded",
                             );
+                            ui.radio_value(&mut location.content_type, ContentType::Raw, "raw");
+                            ui.radio_value(
+                                &mut location.content_type,
+                                ContentType::Binary,
+                                "binary",
+                            );
                         });
-                        if location.content_type == ContentType::Json {
+                        if location.content_type == ContentType::Raw {
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_source("raw_type")
+                                    .selected_text(match location.raw_type {
+                                        RawType::Custom => "custom",
+                                        raw_type => raw_type.mime(),
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        for raw_type in [
+                                            RawType::Text,
+                                            RawType::Xml,
+                                            RawType::Html,
+                                            RawType::JavaScript,
+                                        ] {
+                                            ui.selectable_value(
+                                                &mut location.raw_type,
+                                                raw_type,
+                                                raw_type.mime(),
+                                            );
+                                        }
+                                        ui.selectable_value(
+                                            &mut location.raw_type,
+                                            RawType::Custom,
+                                            "custom",
+                                        );
+                                    });
+                                if location.raw_type == RawType::Custom {
+                                    ui.add(
+                                        egui::TextEdit::singleline(
+                                            &mut location.custom_content_type,
+                                        )
+                                        .hint_text("content type"),
+                                    );
+                                }
+                            });
+                        }
+                        if location.content_type == ContentType::Binary {
+                            ui.horizontal(|ui| {
+                                if ui.button("Select file...").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                        location.binary_file = path.display().to_string();
+                                    }
+                                }
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut location.binary_file)
+                                        .hint_text("path of the file to send")
+                                        .desired_width(f32::INFINITY),
+                                );
+                            });
+                        } else if location.content_type == ContentType::FormData {
+                            ui_form_data(ui, &mut location.form_data);
+                        } else if matches!(
+                            location.content_type,
+                            ContentType::Json | ContentType::Raw
+                        ) {
                             ScrollArea::vertical()
                                 .id_source("source")
                                 .max_height(200.0)
@@ -527,85 +1245,46 @@ impl TabViewer for MyContext<'_> {
                                     );
                                 });
                         } else {
-                            ui.horizontal(|ui| {
-                                ui.label("Request Body");
-                                if ui.button("add").clicked() {
-                                    add_location = true;
-                                    location.form_params.push(("".to_owned(), "".to_owned()));
-                                    ui.end_row();
-                                }
-                            });
-                            egui::Grid::new("request_body")
-                                .num_columns(3)
-                                .min_col_width(300.0)
-                                .spacing(egui::vec2(
-                                    ui.spacing().item_spacing.x * 0.5,
-                                    ui.spacing().item_spacing.x * 0.5,
-                                ))
-                                .show(ui, |ui| {
-                                    // ui.horizontal(|ui| {
-                                    if location.form_params.is_empty() {
-                                        location.form_params.push(("".to_owned(), "".to_owned()));
-                                        // });
-                                        ui.end_row();
-                                    }
-
-                                    let mut i = 0 as usize;
-                                    while i < location.form_params.len() {
-                                        ui.add(egui::TextEdit::singleline(
-                                            &mut location.form_params[i].0,
-                                        ));
-                                        ui.add(egui::TextEdit::singleline(
-                                            &mut location.form_params[i].1,
-                                        ));
-                                        if ui.button("del").clicked() {
-                                            location.form_params.remove(i);
-                                        }
-                                        i = i + 1;
-                                        ui.end_row();
-                                    }
-                                });
+                            ui_key_values(
+                                ui,
+                                "request_body",
+                                "Request Body",
+                                &mut location.form_params,
+                            );
                         }
                     }
                     RequestEditor::Headers => {
-                        ui.horizontal(|ui| {
-                            ui.label("Headers");
-                            if ui.button("add").clicked() {
-                                add_location = true;
-                                location.header.push(("".to_owned(), "".to_owned()));
-                                ui.end_row();
-                            }
-                        });
-                        egui::Grid::new("query_headers")
-                            .num_columns(3)
-                            .min_col_width(300.0)
-                            .spacing(egui::vec2(
-                                ui.spacing().item_spacing.x * 0.5,
-                                ui.spacing().item_spacing.x * 0.5,
-                            ))
-                            .show(ui, |ui| {
-                                // ui.horizontal(|ui| {
-                                if location.header.is_empty() {
-                                    location.header.push(("".to_owned(), "".to_owned()));
-                                    // });
-                                    ui.end_row();
-                                }
-
-                                let mut i = 0 as usize;
-                                while i < location.header.len() {
-                                    ui.add(egui::TextEdit::singleline(&mut location.header[i].0));
-                                    ui.add(egui::TextEdit::singleline(&mut location.header[i].1));
-                                    if ui.button("del").clicked() {
-                                        location.header.remove(i);
-                                    }
-                                    i = i + 1;
-                                    ui.end_row();
-                                }
-                            });
+                        ui_key_values(ui, "query_headers", "Headers", &mut location.header);
+                    }
+                    RequestEditor::Auth => {
+                        let inherit = format!("directory: {}", directory_auth.kind.text());
+                        ui_auth(ui, "location_auth", &mut location.auth, &inherit);
+                        let auth = Auth::effective([&location.auth, &directory_auth]);
+                        if auth.kind == AuthKind::OAuth2 {
+                            let config = auth.oauth2.resolved(|s| scope.resolve(s));
+                            ui_oauth_token(ui, oauth_tokens, &config);
+                        }
+                    }
+                    RequestEditor::Tests => {
+                        ui_assertions(ui, &mut location.assertions);
+                    }
+                    RequestEditor::Scripts => {
+                        ui_scripts(
+                            ui,
+                            "location_scripts",
+                            &mut location.pre_request_script,
+                            &mut location.test_script,
+                        );
+                    }
+                    RequestEditor::Settings => {
+                        ui_request_settings(ui, location, self.settings);
                     }
                 }
 
-                ui_resource(ui, &location.response);
+                if let Some(failure) = &location.failure {
+                    ui_failure(ui, failure);
+                }
+                ui_resource(ui, &location.response, &location.assertion_results);
             });
     }
 
@@ -628,11 +1307,12 @@ impl TabViewer for MyContext<'_> {
             url: ("https://httpbin.org/get".into()),
             params: (Vec::new()),
             body: ("".into()),
-            header: (vec![("".to_owned(), "".to_owned())]),
+            header: (vec![KeyValue::default()]),
             content_type: ContentType::Json,
             form_params: Vec::new(),
             method: Method::Get,
             response: Default::default(),
+            ..Default::default()
         };
         self.api_collection.buffers.insert(id, location.clone());
         self.added_nodes.push(location);
@@ -650,10 +1330,51 @@ pub struct HttpApp {
     tree: DockState<String>,
     api_collection: ApiCollection,
     reqest_editor: RequestEditor,
+    /// Variables visible to every request.
+    globals: Vec<(String, String)>,
+    environments: Vec<Environment>,
+    /// Id of the environment in `environments` used to resolve `{{variables}}`.
+    active_environment: Option<String>,
+    settings: Settings,
+    #[serde(skip)]
+    show_environments: bool,
+    #[serde(skip)]
+    show_settings: bool,
+    #[serde(skip)]
+    show_cookies: bool,
     #[serde(skip)]
-    sender: mpsc::Sender<Resource>,
+    show_history: bool,
+    /// Executed requests, stored in their own file, see [`history`].
     #[serde(skip)]
-    receiver: mpsc::Receiver<Resource>,
+    history: History<Location>,
+    #[serde(skip)]
+    history_filter: history::Filter,
+    #[serde(skip)]
+    pending_sends: Vec<String>,
+    #[serde(skip)]
+    show_compare: bool,
+    #[serde(skip)]
+    show_console: bool,
+    #[serde(skip)]
+    show_runner: bool,
+    runner: RunnerConfig,
+    /// The last run of a directory, see [`runner`].
+    #[serde(skip)]
+    run: Option<Run>,
+    /// Output of the scripts, the last [`CONSOLE_LINES`].
+    #[serde(skip)]
+    console: Vec<LogLine>,
+    /// The old and the new response of the Compare window.
+    #[serde(skip)]
+    compare: [Option<ResponseSource>; 2],
+    #[serde(skip)]
+    dir_variables: String,
+    #[serde(skip)]
+    sender: mpsc::Sender<Finished>,
+    #[serde(skip)]
+    receiver: mpsc::Receiver<Finished>,
+    #[serde(skip)]
+    toasts: Toasts,
     // context: MyContext<'a>,
     picked_path: Option<String>,
     #[serde(skip)]
@@ -665,7 +1386,7 @@ pub struct HttpApp {
     #[serde(skip)]
     preview: Option<Vec<Color>>,
     #[serde(skip)]
-    run_state: Vec<RunState>,
+    run_state: BTreeMap<String, RunState>,
     #[serde(skip)]
     pending_commands: Vec<Command>,
     #[serde(skip)]
@@ -683,8 +1404,28 @@ impl Default for HttpApp {
             tree: DockState::new(vec![]),
             api_collection: Default::default(),
             reqest_editor: Default::default(),
+            globals: Default::default(),
+            environments: Default::default(),
+            active_environment: None,
+            settings: Default::default(),
+            show_environments: false,
+            show_settings: false,
+            show_cookies: false,
+            show_history: false,
+            history: Default::default(),
+            history_filter: Default::default(),
+            pending_sends: Default::default(),
+            show_compare: false,
+            show_console: false,
+            show_runner: false,
+            runner: Default::default(),
+            run: None,
+            console: Default::default(),
+            compare: Default::default(),
+            dir_variables: Default::default(),
             sender,
             receiver,
+            toasts: Default::default(),
             // context: MyContext::default(),
             picked_path: Default::default(),
             show_confirmation_dialog: false,
@@ -715,13 +1456,303 @@ impl HttpApp {
     pub fn new(re_ui: ReUi, storage: Option<&dyn eframe::Storage>) -> Self {
         setup_custom_fonts(&re_ui.egui_ctx);
         if let Some(storage) = storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+            let mut http_app: HttpApp =
+                eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+            // The url was sent before the params became the source of truth,
+            // params it could not parse were never filled in.
+            for location in http_app.api_collection.buffers.values_mut() {
+                location.params = query::sync(&location.url, &location.params);
+            }
+            executor::remove_stale_spill_files(
+                &std::env::temp_dir(),
+                http_app
+                    .api_collection
+                    .buffers
+                    .values()
+                    .filter_map(|location| location.response.as_ref()),
+            );
+            http_app.load_history();
+            return http_app;
         }
 
         let mut http_app: HttpApp = Default::default();
         http_app.re_ui = re_ui;
+        executor::remove_stale_spill_files(&std::env::temp_dir(), []);
+        http_app.load_history();
         return http_app;
     }
+
+    fn load_history(&mut self) {
+        match History::load(history::default_path()) {
+            Ok(history) => self.history = history,
+            Err(err) => {
+                self.toasts.add(Toast {
+                    kind: ToastKind::Warning,
+                    text: format!("Cannot read the history: {err}"),
+                    options: ToastOptions::with_ttl_in_seconds(4.0),
+                });
+            }
+        }
+    }
+
+    /// Apply the output of the test scripts of the `sent` request, returns the
+    /// names of the failed tests.
+    fn apply_test_output(&mut self, sent: &Location, output: ScriptOutput) -> Vec<String> {
+        let failed: Vec<String> = output
+            .tests()
+            .filter(|(_, passed)| !passed)
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        if !failed.is_empty() {
+            self.toasts.add(Toast {
+                kind: ToastKind::Warning,
+                text: format!(
+                    "{}: {} of {} tests failed",
+                    sent.name,
+                    failed.len(),
+                    output.tests().count()
+                ),
+                options: ToastOptions::with_ttl_in_seconds(4.0),
+            });
+        }
+        self.apply_script_output(output);
+        failed
+    }
+
+    /// Store the variables set by scripts in the active environment, or in the
+    /// globals without one, and log their output to the console.
+    fn apply_script_output(&mut self, output: ScriptOutput) {
+        let environment = self
+            .active_environment
+            .as_ref()
+            .and_then(|id| self.environments.iter_mut().find(|e| &e.id == id));
+        let variables = match environment {
+            Some(environment) => &mut environment.variables,
+            None => &mut self.globals,
+        };
+        for (name, value) in output.variables {
+            match variables.iter_mut().find(|(key, _)| key.trim() == name) {
+                Some((_, old)) => *old = value,
+                None => variables.push((name, value)),
+            }
+        }
+        if let Some(error) = output.error {
+            self.toasts.add(Toast {
+                kind: ToastKind::Error,
+                text: error,
+                options: ToastOptions::with_ttl_in_seconds(4.0),
+            });
+        }
+        self.console.extend(output.log);
+        let excess = self.console.len().saturating_sub(CONSOLE_LINES);
+        self.console.drain(..excess);
+    }
+
+    /// Send the steps of the current run that are due, see [`Run::due`].
+    fn run_collection(&mut self, ctx: &egui::Context) {
+        let Some(run) = &mut self.run else {
+            return;
+        };
+        let now = Instant::now();
+        // Steps cancelled from their tab never report back.
+        let cancelled: Vec<String> = run
+            .running()
+            .filter(|id| !self.run_state.contains_key(*id))
+            .map(str::to_owned)
+            .collect();
+        for id in cancelled {
+            run.finish(&id, None, None, vec!["cancelled".to_owned()], now);
+        }
+        let due = run.due(now, |id| self.run_state.contains_key(id));
+        if run.is_running() {
+            // For the delay between steps.
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        for id in due {
+            if let Err(failure) = self.send_step(ctx, &id) {
+                if let Some(run) = &mut self.run {
+                    run.finish(&id, None, None, vec![failure], Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Send the request `id` like its tab would, with the active environment.
+    fn send_step(&mut self, ctx: &egui::Context, id: &str) -> Result<(), String> {
+        let environment = self
+            .active_environment
+            .as_ref()
+            .and_then(|id| self.environments.iter().find(|e| &e.id == id));
+        let scope = variable_scope(
+            &self.globals,
+            &self.api_collection,
+            &self.directory,
+            environment,
+            id,
+        );
+        let location = self
+            .api_collection
+            .buffers
+            .get(id)
+            .ok_or_else(|| "request not found".to_owned())?;
+        Outgoing::new(
+            id,
+            location,
+            scope,
+            &self.settings,
+            &self.api_collection,
+            &self.directory,
+            environment,
+        )
+        .send(ctx, &self.sender, &mut self.run_state);
+        Ok(())
+    }
+
+    /// Record a finished request in the history.
+    fn record_history(
+        &mut self,
+        mut location: Location,
+        environment: Option<String>,
+        outcome: &Outcome,
+    ) {
+        let (status, elapsed) = match outcome {
+            Outcome::Response(resource) => {
+                location.response = Some(history::stored_resource(resource));
+                (Some(resource.status), resource.elapsed)
+            }
+            Outcome::Failure(failure) => {
+                location.failure = Some(failure.clone());
+                (None, failure.elapsed)
+            }
+        };
+        let entry = HistoryEntry {
+            id: Uuid::new_v4().to_string(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            environment,
+            method: location.method.to_text(),
+            url: query::with_params(&location.url, &location.params),
+            status,
+            elapsed,
+            location,
+        };
+        if let Err(err) = self.history.push(entry) {
+            self.toasts.add(Toast {
+                kind: ToastKind::Warning,
+                text: format!("Cannot save the history: {err}"),
+                options: ToastOptions::with_ttl_in_seconds(4.0),
+            });
+        }
+    }
+
+    /// Open the request of a history entry in a new tab, returns the id of the tab.
+    fn restore_history_entry(&mut self, entry_id: &str) -> Option<String> {
+        let entry = self.history.get(entry_id)?;
+        let id = Uuid::new_v4().to_string();
+        let location = Location {
+            id: id.clone(),
+            ..entry.location.clone()
+        };
+        self.api_collection.buffers.insert(id.clone(), location);
+        self.tree.push_to_focused_leaf(id.clone());
+        Some(id)
+    }
+    /// Store finished requests on the `Location` that sent them.
+    fn receive_outcomes(&mut self) {
+        while let Ok(finished) = self.receiver.try_recv() {
+            let Finished {
+                id,
+                cancel,
+                mut sent,
+                outcome,
+                token,
+                pre_request,
+                tests,
+            } = finished;
+            // Keep tokens and variables even if the request itself was cancelled.
+            if let Some((config, token)) = token {
+                self.api_collection.oauth_tokens.insert(&config, token);
+            }
+            if let Some(output) = pre_request {
+                self.apply_script_output(output);
+            }
+            // Ignore results of cancelled requests.
+            match self.run_state.get(&id) {
+                Some(RunState::Running {
+                    cancel: current, ..
+                }) if current == &cancel => {}
+                _ => continue,
+            }
+            let mut assertion_results = Vec::new();
+            if let Some(RunState::Running { environment, .. }) = self.run_state.remove(&id) {
+                let mut failures = Vec::new();
+                if let Some(output) = tests {
+                    failures = self.apply_test_output(&sent, output);
+                }
+                let (status, elapsed) = match &outcome {
+                    Outcome::Response(resource) => {
+                        assertion_results = assertion::evaluate(&sent.assertions, resource);
+                        sent.assertion_results = assertion_results.clone();
+                        (Some(resource.status), resource.elapsed)
+                    }
+                    Outcome::Failure(failure) => {
+                        failures.push(failure.message.clone());
+                        (None, failure.elapsed)
+                    }
+                };
+                if let Some(run) = &mut self.run {
+                    failures.extend(
+                        assertion_results
+                            .iter()
+                            .filter(|r| !r.passed)
+                            .map(|r| r.name.clone()),
+                    );
+                    run.finish(&id, status, Some(elapsed), failures, Instant::now());
+                }
+                // Nothing was sent if a pre-request script failed.
+                if !matches!(&outcome, Outcome::Failure(f) if f.kind == FailureKind::Script) {
+                    self.record_history(sent, environment, &outcome);
+                }
+            }
+            let Some(location) = self.api_collection.buffers.get_mut(&id) else {
+                continue;
+            };
+            if let Some(previous) = location.response.take() {
+                previous.remove_spill_file();
+            }
+            location.assertion_results = assertion_results;
+            let cookies = &mut self.api_collection.cookies;
+            let redirects = match &outcome {
+                Outcome::Response(resource) => &resource.redirects,
+                Outcome::Failure(failure) => &failure.redirects,
+            };
+            for redirect in redirects {
+                for set_cookie in &redirect.set_cookies {
+                    cookies.store(&redirect.url, set_cookie);
+                }
+            }
+            if let Outcome::Response(resource) = &outcome {
+                cookies.store_response(&resource.url, &resource.headers);
+            }
+            match outcome {
+                Outcome::Response(resource) => {
+                    location.response = Some(*resource);
+                    location.failure = None;
+                }
+                Outcome::Failure(failure) => {
+                    self.toasts.add(Toast {
+                        kind: ToastKind::Error,
+                        text: format!("{}: {}", location.name, failure.message),
+                        options: ToastOptions::with_ttl_in_seconds(4.0),
+                    });
+                    location.failure = Some(failure);
+                }
+            }
+        }
+    }
+
     pub fn nested_menus(ui: &mut egui::Ui) {
         if ui.button("Open...").clicked() {
             ui.close_menu();
@@ -736,6 +1767,23 @@ impl eframe::App for HttpApp {
     }
 
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.receive_outcomes();
+        self.run_collection(ctx);
+
+        // Escape is left to other widgets unless the focused tab is running.
+        let running = self
+            .tree
+            .find_active_focused()
+            .map(|(_, id)| id.clone())
+            .filter(|id| matches!(self.run_state.get(id), Some(RunState::Running { .. })));
+        if let (Some(id), Some(shortcut)) = (running, Command::CancelRequest.kb_shortcut()) {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                if let Some(location) = self.api_collection.buffers.get_mut(&id) {
+                    cancel_request(&mut self.run_state, location);
+                }
+            }
+        }
+
         TopBottomPanel::bottom("http_bottom")
             .resizable(false)
             .show(ctx, |ui| {
@@ -766,6 +1814,59 @@ impl eframe::App for HttpApp {
             .show(ctx, |ui| {
                 ScrollArea::vertical().show(ui, |ui| {
                     ui.horizontal(|ui| {
+                        ui_environment_switcher(
+                            ui,
+                            &self.environments,
+                            &mut self.active_environment,
+                        );
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::Settings)
+                            .on_hover_text("Manage environments and variables")
+                            .clicked()
+                        {
+                            self.show_environments = true;
+                        }
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::Tune)
+                            .on_hover_text("Settings")
+                            .clicked()
+                        {
+                            self.show_settings = true;
+                        }
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::Storage)
+                            .on_hover_text("Cookies")
+                            .clicked()
+                        {
+                            self.show_cookies = true;
+                        }
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::History)
+                            .on_hover_text("History")
+                            .clicked()
+                        {
+                            self.show_history = !self.show_history;
+                        }
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::Compare)
+                            .on_hover_text("Compare responses")
+                            .clicked()
+                        {
+                            self.show_compare = true;
+                        }
+                        if self
+                            .re_ui
+                            .small_icon_button(ui, &Icon::Code)
+                            .on_hover_text("Script console")
+                            .clicked()
+                        {
+                            self.show_console = true;
+                        }
                         // egui::widgets::global_dark_light_mode_switch(ui);
                         // if self.darkmode {
                         //     if ui
@@ -825,13 +1926,13 @@ impl eframe::App for HttpApp {
                                             id: item.id.clone(),
                                             name: (item.name.clone()),
                                             url: (item.request.url.raw.clone()),
-                                            params: (Vec::new()),
+                                            params: query::params(&item.request.url.raw),
                                             body: (item.request.body.raw),
                                             header: (item
                                                 .request
                                                 .header
                                                 .into_iter()
-                                                .map(|i| (i.key, i.value))
+                                                .map(|i| KeyValue::new(i.key, i.value))
                                                 .collect()),
                                             content_type: ContentType::Json,
                                             form_params: item
@@ -839,10 +1940,11 @@ impl eframe::App for HttpApp {
                                                 .body
                                                 .urlencoded
                                                 .into_iter()
-                                                .map(|f| (f.key, f.value))
+                                                .map(|f| KeyValue::new(f.key, f.value))
                                                 .collect(),
                                             method: Method::from_text(item.request.method),
                                             response: Default::default(),
+                                            ..Default::default()
                                         };
                                         self.api_collection
                                             .buffers
@@ -885,11 +1987,12 @@ impl eframe::App for HttpApp {
                                             url: ("https://httpbin.org/get".into()),
                                             params: (Vec::new()),
                                             body: ("".into()),
-                                            header: (vec![("".to_owned(), "".to_owned())]),
+                                            header: (vec![KeyValue::default()]),
                                             content_type: ContentType::Json,
                                             form_params: Vec::new(),
                                             method: Method::Get,
                                             response: Default::default(),
+                                            ..Default::default()
                                         };
                                         dir.1.locations.push(id.clone());
                                         self.api_collection.buffers.insert(id, location.clone());
@@ -901,6 +2004,27 @@ impl eframe::App for HttpApp {
                                         self.dir_rename = dir.0.clone();
                                         self.show_confirmation_dialog = true;
                                     }
+                                    Command::EditVariables => {
+                                        self.dir_variables = dir.0.clone();
+                                    }
+                                    Command::RunDirectory => {
+                                        // Keep a run in progress visible.
+                                        if !self.run.as_ref().is_some_and(Run::is_running) {
+                                            let requests = dir
+                                                .1
+                                                .locations
+                                                .iter()
+                                                .filter_map(|id| {
+                                                    let location =
+                                                        self.api_collection.buffers.get(id)?;
+                                                    Some((id.clone(), location.name.clone()))
+                                                })
+                                                .collect();
+                                            self.run = Some(Run::new(dir.1.name.clone(), requests));
+                                        }
+                                        self.show_runner = true;
+                                    }
+                                    Command::CancelRequest => {}
                                 }
                             }
 
@@ -997,6 +2121,65 @@ impl eframe::App for HttpApp {
                         });
                     }
                     self.directory.retain(|v, _| v != &dir_del);
+                    if !self.dir_variables.is_empty() {
+                        let mut open = true;
+                        if let Some(dir) = self.directory.get_mut(&self.dir_variables) {
+                            egui::Window::new(format!("{} variables", dir.name))
+                                .id(egui::Id::new("dir_variables"))
+                                .open(&mut open)
+                                .show(ctx, |ui| {
+                                    ui_variables(ui, "dir_variables_grid", &mut dir.variables);
+                                    ui.separator();
+                                    ui.label("Proxy");
+                                    ui_proxy(ui, "dir_proxy", &mut dir.proxy, "collection");
+                                    ui.separator();
+                                    ui.label("Auth");
+                                    ui_auth(ui, "dir_auth", &mut dir.auth, "no auth");
+                                    ui.separator();
+                                    ui_scripts(
+                                        ui,
+                                        "dir_scripts",
+                                        &mut dir.pre_request_script,
+                                        &mut dir.test_script,
+                                    );
+                                });
+                        }
+                        if !open {
+                            self.dir_variables = Default::default();
+                        }
+                    }
+                    if self.show_environments {
+                        egui::Window::new("Environments")
+                            .open(&mut self.show_environments)
+                            .vscroll(true)
+                            .show(ctx, |ui| {
+                                ui_environments(
+                                    ui,
+                                    &mut self.globals,
+                                    &mut self.api_collection,
+                                    &mut self.environments,
+                                    &mut self.active_environment,
+                                );
+                            });
+                    }
+                    egui::Window::new("Settings")
+                        .open(&mut self.show_settings)
+                        .show(ctx, |ui| ui_settings(ui, &mut self.settings));
+                    if let Some(run) = &mut self.run {
+                        egui::Window::new(format!("Run {}", run.name))
+                            .id(egui::Id::new("runner"))
+                            .open(&mut self.show_runner)
+                            .vscroll(true)
+                            .show(ctx, |ui| ui_runner(ui, &mut self.runner, run));
+                    }
+                    egui::Window::new("Console")
+                        .open(&mut self.show_console)
+                        .vscroll(true)
+                        .show(ctx, |ui| ui_console(ui, &mut self.console));
+                    egui::Window::new("Cookies")
+                        .open(&mut self.show_cookies)
+                        .vscroll(true)
+                        .show(ctx, |ui| ui_cookies(ui, &mut self.api_collection.cookies));
                     if self.show_confirmation_dialog {
                         egui::Window::new("")
                             .collapsible(false)
@@ -1016,6 +2199,56 @@ impl eframe::App for HttpApp {
                 });
             });
 
+        if self.show_history {
+            let action = SidePanel::right("history_panel")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    ui_history(ui, &self.history, &mut self.history_filter)
+                })
+                .inner;
+            let result = match action {
+                Some(HistoryAction::Open(entry_id)) => {
+                    self.restore_history_entry(&entry_id);
+                    Ok(())
+                }
+                Some(HistoryAction::Resend(entry_id)) => {
+                    if let Some(id) = self.restore_history_entry(&entry_id) {
+                        self.pending_sends.push(id);
+                    }
+                    Ok(())
+                }
+                Some(HistoryAction::Compare(entry_id)) => {
+                    // The entry picked before becomes the old response.
+                    let [_, new] = std::mem::take(&mut self.compare);
+                    self.compare = [new, Some(ResponseSource::History(entry_id))];
+                    self.show_compare = true;
+                    Ok(())
+                }
+                Some(HistoryAction::Remove(entry_id)) => self.history.remove(&entry_id),
+                Some(HistoryAction::Clear) => self.history.clear(),
+                None => Ok(()),
+            };
+            if let Err(err) = result {
+                self.toasts.add(Toast {
+                    kind: ToastKind::Warning,
+                    text: format!("Cannot save the history: {err}"),
+                    options: ToastOptions::with_ttl_in_seconds(4.0),
+                });
+            }
+        }
+
+        egui::Window::new("Compare responses")
+            .open(&mut self.show_compare)
+            .default_size([900.0, 600.0])
+            .show(ctx, |ui| {
+                ui_compare(
+                    ui,
+                    &self.api_collection.buffers,
+                    &self.history,
+                    &mut self.compare,
+                )
+            });
+
         let mut added_nodes = Vec::new();
         DockArea::new(&mut self.tree)
             .show_add_buttons(true)
@@ -1024,17 +2257,24 @@ impl eframe::App for HttpApp {
                 ctx,
                 &mut MyContext {
                     api_collection: &mut self.api_collection,
+                    directory: &self.directory,
+                    globals: &self.globals,
+                    environment: self
+                        .active_environment
+                        .as_ref()
+                        .and_then(|id| self.environments.iter().find(|e| &e.id == id)),
+                    settings: &self.settings,
                     // name: String,
                     reqest_editor: &mut self.reqest_editor,
                     // #[serde(skip)]
                     sender: &self.sender,
                     // #[serde(skip)]
-                    receiver: &self.receiver,
-                    // #[serde(skip)]
                     added_nodes: &mut added_nodes,
                     run_state: &mut self.run_state,
+                    pending_sends: &mut self.pending_sends,
                 },
             );
+        self.toasts.show(ctx);
         added_nodes.drain(..).for_each(|node| {
             // self.tree.set_focused_node(node);
             self.tree.push_to_focused_leaf(node.id);
@@ -1047,7 +2287,13 @@ impl eframe::App for HttpApp {
     }
 }
 
-fn ui_url(ui: &mut egui::Ui, location: &mut Location) -> bool {
+/// `directory_auth` is the auth the location inherits.
+fn ui_url(
+    ui: &mut egui::Ui,
+    location: &mut Location,
+    scope: &VariableScope,
+    directory_auth: &Auth,
+) -> bool {
     let mut trigger_fetch = false;
 
     ui.text_edit_singleline(&mut location.name);
@@ -1071,181 +2317,1550 @@ fn ui_url(ui: &mut egui::Ui, location: &mut Location) -> bool {
             .add(egui::TextEdit::singleline(&mut location.url).desired_width(800.0))
             .changed()
         {
-            if location.url.ends_with("&") {
-                if !location.params.contains(&("".to_string(), "".to_string())) {
-                    location.params.push(("".to_string(), "".to_string()));
-                }
-            } else {
-                let urlstr = location.url.clone();
-                let url = uri(&urlstr);
-                if url.is_ok() {
-                    let url = url.unwrap().1;
-                    location.params.drain(..);
-                    url.query.unwrap_or_default().into_iter().for_each(|q| {
-                        location
-                            .params
-                            .push((q.0.to_string().clone(), q.1.to_string().clone()));
-                    })
-                }
-                // let req = ParsedRequest::new(req).unwrap();
-                // let url = parse_url(&urlstr);
-                // let url = URL::parse(&urlstr).unwrap();
-            }
-        }
-        // let urlstr = location.url.clone();
-        // let url = uri(&urlstr);
-        // if url.is_ok() {
-        //     let mut url = url.ok().unwrap().1;
-        //     if url.query.is_some() {
-        //         url.query.as_mut().unwrap().clear();
-        //         location.params.iter().for_each(|p| {
-        //             if p.0.len() > 0 {
-        //                 url.query.as_mut().unwrap().push((&p.0, &p.1));
-        //             }
-        //         });
-        //     } else {
-        //         url.query = Some(vec![]);
-        //         location.params.iter().for_each(|p| {
-        //             if p.0.len() > 0 {
-        //                 url.query.as_mut().unwrap().push((&p.0, &p.1));
-        //             }
-        //         });
-        //     }
-        //     if location.url.ends_with("&") {
-        //         location.url = url.to_string() + "&";
-        //     } else {
-        //         location.url = url.to_string()
-        //     }
-        // }
-        // let urlstr = location.url.clone();
-        // let url = uri(&urlstr);
-        // if url.is_ok() {
-        //     let mut url = url.ok().unwrap().1;
-        //     if url.query.is_some() {
-        //         url.query.as_mut().unwrap().clear();
-        //         location.params.iter().for_each(|p| {
-        //             if p.0.len() > 0 {
-        //                 url.query.as_mut().unwrap().push((&p.0, &p.1));
-        //             }
-        //         });
-        //     }
-        //     location.url = url.to_string();
-        // }
-        // let url = URL::parse(&location.url);
-        // if url.is_ok() {
-        //     let mut url = url.ok().unwrap();
-        //     url.query_pairs_mut().clear();
-        //     location.params.clone().into_iter().for_each(|p| {
-        //         if p.0.len() > 0 {
-        //             url.query_pairs_mut().append_pair(&p.0, &p.1);
-        //         }
-        //     });
-
-        //     // if location.url.ends_with("&") {
-        //     //     location.url = url_escape::decode(&url).to_string() + "&";
-        //     // } else {
-        //     //     location.url = url_escape::decode(&url).to_string();
-        //     // }
-        // }
-
-        // let parsed = Url::parse(&location.url).unwrap();
-        // let url: &str = &parsed[..url::Position::AfterPath];
-        // let mut request = ureq::request("get", &urlstr);
-        // for e in &location.params {
-        //     request = request.query(&e.0, &e.1);
-        // }
-        // location.url = request.url().to_string();
+            location.params = query::sync(&location.url, &location.params);
+        }
         if ui.button("Go").clicked() {
             trigger_fetch = true;
         }
-        let tooltip = "Click to copy the curl command";
-        if ui.button("curl").on_hover_text(tooltip).clicked() {
-            let mut curl = format!("curl '{}'", location.url);
-
-            if location.content_type == ContentType::FormUrlEncoded {
-                location
-                    .form_params
-                    .iter()
-                    .filter(|f| !f.0.is_empty() && !f.1.is_empty())
-                    .for_each(|h| {
-                        curl = format!("{} -d '{}={}'", curl, h.0, h.1);
-                    });
+        ui.menu_button("curl", |ui| {
+            if ui
+                .button("Copy resolved")
+                .on_hover_text("Copy the curl command with all variables replaced")
+                .clicked()
+            {
+                let auth = Auth::effective([&location.auth, directory_auth]);
+                let curl = curl_command(
+                    &location.resolved(scope),
+                    &auth.resolved(|s| scope.resolve(s)),
+                );
+                ui.output_mut(|u| u.copied_text = curl);
+                ui.close_menu();
             }
-
-            if location.method != Method::Get {
-                curl = format!("{} -X '{}'", curl, location.method.to_text());
+            if ui
+                .button("Copy templated")
+                .on_hover_text("Copy the curl command keeping the {{variables}}")
+                .clicked()
+            {
+                let curl =
+                    curl_command(location, &Auth::effective([&location.auth, directory_auth]));
+                ui.output_mut(|u| u.copied_text = curl);
+                ui.close_menu();
             }
+        });
+    });
 
-            location
-                .header
-                .iter()
-                .filter(|f| !f.0.is_empty() && !f.1.is_empty())
-                .for_each(|h| {
-                    curl = format!("{} -H '{}:{}'", curl, h.0, h.1);
-                });
+    let unresolved = unresolved_variables(scope, &location.url);
+    if !unresolved.is_empty() {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("Undefined variables: {}", unresolved.join(", ")),
+        );
+    }
 
-            if location.content_type == ContentType::Json && !location.body.is_empty() {
-                curl = format!(
-                    "{} -H 'Content-Type: application/json' -d '{}'",
-                    curl, location.body
-                );
-            }
+    trigger_fetch
+}
 
-            ui.output_mut(|u| u.copied_text = curl);
+fn ui_progress(ui: &mut egui::Ui, progress: &Progress) {
+    let received = progress.received();
+    match progress.total() {
+        Some(total) => {
+            ui.add(
+                egui::ProgressBar::new(received as f32 / total as f32)
+                    .desired_width(200.0)
+                    .text(format!(
+                        "{} / {}",
+                        format_size(received),
+                        format_size(total)
+                    )),
+            );
         }
-    });
-
-    trigger_fetch
+        None if received > 0 => {
+            ui.spinner();
+            ui.label(format_size(received));
+        }
+        None => {
+            ui.spinner();
+        }
+    }
 }
 
-fn ui_resource(ui: &mut egui::Ui, resource: &Option<Resource>) {
-    if let Some(resource) = resource {
-        ui.monospace(format!("url:          {}", resource.url));
-        ui.monospace(format!(
-            "status:       {} ({})",
-            resource.status, resource.status_text
-        ));
-        ui.monospace(format!("content-type: {}", resource.content_type));
-        ui.monospace(format!(
-            "size:         {:.1} kB",
-            resource.length as f32 / 1000.0
-        ));
-        ui.monospace(format!("time:         {} ms", resource.elapsed));
-        ui.separator();
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=999 => format!("{bytes} B"),
+        1_000..=999_999 => format!("{:.1} kB", bytes as f32 / 1e3),
+        _ => format!("{:.1} MB", bytes as f32 / 1e6),
+    }
+}
 
-        egui::ScrollArea::vertical()
-            .auto_shrink([false; 2])
-            .show(ui, |ui| {
-                egui::CollapsingHeader::new("Response headers")
-                    .default_open(false)
-                    .show(ui, |ui| {
-                        egui::Grid::new("response_headers")
-                            .spacing(egui::vec2(ui.spacing().item_spacing.x * 2.0, 0.0))
-                            .show(ui, |ui| {
-                                for (key, value) in &resource.headers {
-                                    ui.label(key);
-                                    ui.label(value);
-                                    ui.end_row();
-                                }
-                            })
-                    });
+fn ui_environment_switcher(
+    ui: &mut egui::Ui,
+    environments: &[Environment],
+    active_environment: &mut Option<String>,
+) {
+    let selected = active_environment
+        .as_ref()
+        .and_then(|id| environments.iter().find(|e| &e.id == id))
+        .map_or_else(|| "No environment".to_owned(), |e| e.name.clone());
+    egui::ComboBox::from_id_source("active_environment")
+        .width(120.0)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(active_environment, None, "No environment");
+            for environment in environments {
+                ui.selectable_value(
+                    active_environment,
+                    Some(environment.id.clone()),
+                    &environment.name,
+                );
+            }
+        });
+}
 
-                ui.separator();
+fn ui_environments(
+    ui: &mut egui::Ui,
+    globals: &mut Vec<(String, String)>,
+    api_collection: &mut ApiCollection,
+    environments: &mut Vec<Environment>,
+    active_environment: &mut Option<String>,
+) {
+    egui::CollapsingHeader::new("Globals")
+        .default_open(true)
+        .show(ui, |ui| ui_variables(ui, "globals", globals));
+    egui::CollapsingHeader::new("Collection")
+        .default_open(false)
+        .show(ui, |ui| {
+            ui_variables(ui, "collection_variables", &mut api_collection.variables);
+            ui.label("Proxy");
+            ui_proxy(ui, "collection_proxy", &mut api_collection.proxy, "global");
+            ui.label("TLS");
+            ui_tls(ui, "collection_tls", &mut api_collection.tls);
+        });
+    ui.separator();
 
-                let mut body = resource.body.clone();
-                if body.len() < 1 {
-                    return;
+    ui.horizontal(|ui| {
+        ui.label("Environments");
+        if ui.button("add").clicked() {
+            environments.push(Environment::new(format!("env {}", environments.len())));
+        }
+    });
+    let mut env_del = None;
+    for (i, environment) in environments.iter_mut().enumerate() {
+        ui.push_id(&environment.id, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(active_environment, Some(environment.id.clone()), "");
+                ui.text_edit_singleline(&mut environment.name);
+                if ui.button("del").clicked() {
+                    env_del = Some(i);
                 }
-                let body1: Value = serde_json::from_str(&body).unwrap_or_default();
-                body = serde_json::to_string_pretty(&body1).unwrap();
-                let colored_text = syntax_highlighting(ui.ctx(), &body);
+            });
+            ui.indent("variables", |ui| {
+                ui_variables(ui, "environment_variables", &mut environment.variables);
+                egui::CollapsingHeader::new("Proxy")
+                    .id_source("environment_proxy_header")
+                    .show(ui, |ui| {
+                        ui_proxy(ui, "environment_proxy", &mut environment.proxy, "directory");
+                    });
+            });
+        });
+    }
+    if let Some(i) = env_del {
+        let removed = environments.remove(i);
+        if active_environment.as_ref() == Some(&removed.id) {
+            *active_environment = None;
+        }
+    }
+}
+
+/// View and edit the cookie jar of a collection.
+fn ui_cookies(ui: &mut egui::Ui, jar: &mut CookieJar) {
+    ui.horizontal(|ui| {
+        if ui.button("add").clicked() {
+            jar.cookies.push(Cookie {
+                path: "/".to_owned(),
+                host_only: true,
+                ..Default::default()
+            });
+        }
+        if ui.button("remove expired").clicked() {
+            jar.remove_expired();
+        }
+        if ui.button("clear").clicked() {
+            jar.cookies.clear();
+        }
+    });
+    if jar.cookies.is_empty() {
+        ui.weak("No cookies, responses with Set-Cookie add them here");
+        return;
+    }
+    let mut del = None;
+    egui::Grid::new("cookies")
+        .num_columns(8)
+        .striped(true)
+        .show(ui, |ui| {
+            for title in [
+                "name",
+                "value",
+                "domain",
+                "path",
+                "expires",
+                "secure",
+                "http only",
+            ] {
+                ui.strong(title);
+            }
+            ui.end_row();
+            for (i, cookie) in jar.cookies.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut cookie.name).desired_width(100.0));
+                });
+                ui.push_id(("value", i), |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut cookie.value).desired_width(200.0));
+                });
+                ui.push_id(("domain", i), |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut cookie.domain).desired_width(140.0))
+                        .on_hover_text(if cookie.host_only {
+                            "Sent to this host only"
+                        } else {
+                            "Sent to this domain and its subdomains"
+                        });
+                });
+                ui.push_id(("path", i), |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut cookie.path).desired_width(80.0));
+                });
+                let expires = cookie.expires_text();
+                if cookie.is_expired() {
+                    ui.colored_label(ui.visuals().weak_text_color(), expires);
+                } else {
+                    ui.label(expires);
+                }
+                ui.checkbox(&mut cookie.secure, "");
+                ui.checkbox(&mut cookie.http_only, "");
+                if ui.button("del").clicked() {
+                    del = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(i) = del {
+        jar.cookies.remove(i);
+    }
+}
+
+/// What was clicked in the History panel, by entry id.
+enum HistoryAction {
+    Open(String),
+    Resend(String),
+    Compare(String),
+    Remove(String),
+    Clear,
+}
+
+fn ui_history(
+    ui: &mut egui::Ui,
+    history: &History<Location>,
+    filter: &mut history::Filter,
+) -> Option<HistoryAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.strong("History");
+        if ui.button("clear").clicked() {
+            action = Some(HistoryAction::Clear);
+        }
+    });
+    ui.add(
+        egui::TextEdit::singleline(&mut filter.search)
+            .hint_text("search urls")
+            .desired_width(f32::INFINITY),
+    );
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("history_status")
+            .width(80.0)
+            .selected_text(filter.status.text())
+            .show_ui(ui, |ui| {
+                for status in StatusFilter::ALL {
+                    ui.selectable_value(&mut filter.status, status, status.text());
+                }
+            });
+        let any = |value: &str, all: &'static str| match value {
+            "" => all.to_owned(),
+            value => value.to_owned(),
+        };
+        egui::ComboBox::from_id_source("history_method")
+            .width(80.0)
+            .selected_text(any(&filter.method, "any method"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.method, String::new(), "any method");
+                for method in history.methods() {
+                    ui.selectable_value(&mut filter.method, method.clone(), method);
+                }
+            });
+        egui::ComboBox::from_id_source("history_host")
+            .width(120.0)
+            .selected_text(any(&filter.host, "any host"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.host, String::new(), "any host");
+                for host in history.hosts() {
+                    ui.selectable_value(&mut filter.host, host.clone(), host);
+                }
+            });
+    });
+    ui.separator();
+
+    if history.entries().is_empty() {
+        ui.weak("No requests yet, every sent request is added here");
+        return action;
+    }
+    ScrollArea::vertical()
+        .id_source("history_entries")
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            for entry in history.entries().iter().rev().filter(|e| e.matches(filter)) {
+                ui.push_id(&entry.id, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .small_button("open")
+                            .on_hover_text("Open in a new tab")
+                            .clicked()
+                        {
+                            action = Some(HistoryAction::Open(entry.id.clone()));
+                        }
+                        if ui
+                            .small_button("re-send")
+                            .on_hover_text("Open in a new tab and send it again")
+                            .clicked()
+                        {
+                            action = Some(HistoryAction::Resend(entry.id.clone()));
+                        }
+                        if ui
+                            .small_button("compare")
+                            .on_hover_text("Compare with the entry picked before")
+                            .clicked()
+                        {
+                            action = Some(HistoryAction::Compare(entry.id.clone()));
+                        }
+                        if ui.small_button("del").clicked() {
+                            action = Some(HistoryAction::Remove(entry.id.clone()));
+                        }
+                        match entry.status {
+                            Some(status) if status < 400 => ui.monospace(status.to_string()),
+                            Some(status) => ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                egui::RichText::new(status.to_string()).monospace(),
+                            ),
+                            None => ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                egui::RichText::new("ERR").monospace(),
+                            ),
+                        };
+                        ui.monospace(&entry.method);
+                        ui.add(egui::Label::new(&entry.url).truncate(true))
+                            .on_hover_text(format!(
+                                "{}\n{}\n{} ms, environment: {}",
+                                entry.url,
+                                entry.time_text(),
+                                entry.elapsed,
+                                entry.environment.as_deref().unwrap_or("none")
+                            ));
+                    });
+                });
+            }
+        });
+    action
+}
+
+/// A response picked in the Compare window.
+#[derive(Clone, Debug, PartialEq)]
+enum ResponseSource {
+    /// The last response of a tab, by `Location.id`.
+    Tab(String),
+    /// The response of a history entry, by its id.
+    History(String),
+}
+
+impl ResponseSource {
+    /// The response and a text naming it.
+    fn resolve<'a>(
+        &self,
+        buffers: &'a BTreeMap<String, Location>,
+        history: &'a History<Location>,
+    ) -> Option<(String, &'a Resource)> {
+        match self {
+            ResponseSource::Tab(id) => {
+                let location = buffers.get(id)?;
+                let resource = location.response.as_ref()?;
+                Some((format!("tab: {}", location.name), resource))
+            }
+            ResponseSource::History(id) => {
+                let entry = history.get(id)?;
+                let resource = entry.location.response.as_ref()?;
+                let text = format!("{} {} at {}", entry.method, entry.url, entry.time_text());
+                Some((text, resource))
+            }
+        }
+    }
+}
+
+fn ui_compare(
+    ui: &mut egui::Ui,
+    buffers: &BTreeMap<String, Location>,
+    history: &History<Location>,
+    compare: &mut [Option<ResponseSource>; 2],
+) {
+    let sources = buffers
+        .keys()
+        .map(|id| ResponseSource::Tab(id.clone()))
+        .chain(
+            history
+                .entries()
+                .iter()
+                .rev()
+                .map(|e| ResponseSource::History(e.id.clone())),
+        );
+    let choices: Vec<(ResponseSource, String)> = sources
+        .filter_map(|source| {
+            let (text, _) = source.resolve(buffers, history)?;
+            Some((source, text))
+        })
+        .collect();
+    egui::Grid::new("compare_sources")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for (side, label) in compare.iter_mut().zip(["old", "new"]) {
+                ui.label(label);
+                let selected = side
+                    .as_ref()
+                    .and_then(|source| source.resolve(buffers, history))
+                    .map_or("pick a response".to_owned(), |(text, _)| text);
+                egui::ComboBox::from_id_source(("compare_source", label))
+                    .width(500.0)
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for (source, text) in &choices {
+                            ui.selectable_value(side, Some(source.clone()), text);
+                        }
+                    });
+                ui.end_row();
+            }
+        });
+    if ui.button("swap").clicked() {
+        compare.swap(0, 1);
+    }
+    ui.separator();
+
+    let resolved = |side: &Option<ResponseSource>| {
+        side.as_ref()
+            .and_then(|source| source.resolve(buffers, history))
+            .map(|(_, resource)| resource)
+    };
+    let (Some(old), Some(new)) = (resolved(&compare[0]), resolved(&compare[1])) else {
+        ui.weak("Pick two responses of open tabs or of the history");
+        return;
+    };
+
+    egui::Grid::new("compare_summary")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for (name, old, new) in [
+                ("status", old.status.to_string(), new.status.to_string()),
+                ("size", format_size(old.length), format_size(new.length)),
+                (
+                    "time",
+                    format!("{} ms", old.elapsed),
+                    format!("{} ms", new.elapsed),
+                ),
+            ] {
+                ui.monospace(name);
+                ui.monospace(old);
+                ui.monospace(new);
+                ui.end_row();
+            }
+        });
+
+    ScrollArea::vertical()
+        .id_source("compare")
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            let header_changes = diff::headers(&old.headers, &new.headers);
+            egui::CollapsingHeader::new(format!("Headers ({} changes)", header_changes.len()))
+                .default_open(true)
+                .show(ui, |ui| ui_changes(ui, "header_changes", &header_changes));
+
+            if old.is_binary() || new.is_binary() {
+                if old.bytes() == new.bytes() {
+                    ui.label("The bodies are identical");
+                } else {
+                    ui.label("The binary bodies differ");
+                }
+                return;
+            }
+            match &*body_diff(ui.ctx(), &old.body, &new.body) {
+                BodyDiff::Json {
+                    changes,
+                    old: old_json,
+                    new: new_json,
+                } => {
+                    egui::CollapsingHeader::new(format!("Body ({} changes)", changes.len()))
+                        .default_open(true)
+                        .show(ui, |ui| ui_changes(ui, "body_changes", changes));
+                    ui.columns(2, |columns| {
+                        for (ui, text) in columns.iter_mut().zip([old_json, new_json]) {
+                            if let Some(colored_text) = syntax_highlighting(ui.ctx(), text) {
+                                colored_text.ui(ui);
+                            }
+                        }
+                    });
+                }
+                BodyDiff::Lines(rows) => ui_line_diff(ui, rows),
+            }
+        });
+}
+
+/// Memoized [`diff::body`], so large bodies are not compared on every frame.
+fn body_diff(ctx: &egui::Context, old: &str, new: &str) -> Arc<BodyDiff> {
+    #[derive(Default)]
+    struct Differ;
+
+    impl egui::util::cache::ComputerMut<(&str, &str), Arc<BodyDiff>> for Differ {
+        fn compute(&mut self, (old, new): (&str, &str)) -> Arc<BodyDiff> {
+            Arc::new(diff::body(old, new))
+        }
+    }
+
+    type DiffCache = egui::util::cache::FrameCache<Arc<BodyDiff>, Differ>;
+
+    ctx.memory_mut(|mem| mem.caches.cache::<DiffCache>().get((old, new)))
+}
+
+/// Color of added lines and values, removed ones use the error color.
+const ADDED_COLOR: Color32 = Color32::from_rgb(76, 175, 80);
+
+fn ui_changes(ui: &mut egui::Ui, id_source: &str, changes: &[Change]) {
+    if changes.is_empty() {
+        ui.weak("identical");
+        return;
+    }
+    let removed_color = ui.visuals().error_fg_color;
+    egui::Grid::new(id_source)
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            for change in changes {
+                ui.monospace(change.path());
+                match change {
+                    Change::Added { new, .. } => {
+                        ui.label("");
+                        ui.colored_label(ADDED_COLOR, format!("+ {new}"));
+                    }
+                    Change::Removed { old, .. } => {
+                        ui.colored_label(removed_color, format!("- {old}"));
+                        ui.label("");
+                    }
+                    Change::Changed { old, new, .. } => {
+                        ui.colored_label(removed_color, format!("- {old}"));
+                        ui.colored_label(ADDED_COLOR, format!("+ {new}"));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// The old and new lines side by side, removed lines are red and added ones green.
+///
+/// Only the visible rows are laid out, long lines are truncated.
+fn ui_line_diff(ui: &mut egui::Ui, rows: &[(Option<String>, Option<String>)]) {
+    let removed_color = ui.visuals().error_fg_color;
+    let text_color = ui.visuals().text_color();
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    let column_width = (ui.available_width() - ui.spacing().item_spacing.x) / 2.0;
+    ScrollArea::vertical()
+        .id_source("line_diff")
+        .max_height(ui.clip_rect().height())
+        .auto_shrink([false, true])
+        .show_rows(ui, row_height, rows.len(), |ui, range| {
+            for (old, new) in &rows[range] {
+                let changed = old != new;
+                ui.horizontal(|ui| {
+                    for (line, color) in [(old, removed_color), (new, ADDED_COLOR)] {
+                        let color = if changed { color } else { text_color };
+                        let text = egui::RichText::new(line.as_deref().unwrap_or_default())
+                            .monospace()
+                            .color(color);
+                        ui.allocate_ui_with_layout(
+                            egui::vec2(column_width, row_height),
+                            egui::Layout::left_to_right(egui::Align::Center),
+                            |ui| {
+                                ui.set_width(column_width);
+                                ui.add(egui::Label::new(text).truncate(true));
+                            },
+                        );
+                    }
+                });
+            }
+        });
+}
+
+fn ui_assertions(ui: &mut egui::Ui, assertions: &mut Vec<Assertion>) {
+    ui.horizontal(|ui| {
+        ui.label("Tests");
+        if ui.button("add").clicked() {
+            assertions.push(Assertion::default());
+        }
+    });
+    if assertions.is_empty() {
+        ui.weak("Checked after every response, e.g. status == 200 or $.data.id exists");
+        return;
+    }
+    egui::Grid::new("request_assertions")
+        .num_columns(6)
+        .spacing(egui::vec2(
+            ui.spacing().item_spacing.x * 0.5,
+            ui.spacing().item_spacing.x * 0.5,
+        ))
+        .show(ui, |ui| {
+            let mut i = 0;
+            while i < assertions.len() {
+                let assertion = &mut assertions[i];
+                ui.add(toggle_switch(&mut assertion.enabled))
+                    .on_hover_text("Check this assertion");
+                egui::ComboBox::from_id_source(("assertion_target", i))
+                    .width(90.0)
+                    .selected_text(assertion.target.text())
+                    .show_ui(ui, |ui| {
+                        for target in Target::ALL {
+                            ui.selectable_value(&mut assertion.target, target, target.text());
+                        }
+                    });
+                let hint = match assertion.target {
+                    Target::Header => "Content-Type",
+                    _ => "$.data.id",
+                };
+                ui.add_enabled(
+                    assertion.target.has_property(),
+                    egui::TextEdit::singleline(&mut assertion.property)
+                        .hint_text(hint)
+                        .desired_width(160.0),
+                );
+                egui::ComboBox::from_id_source(("assertion_operator", i))
+                    .width(90.0)
+                    .selected_text(assertion.operator.text())
+                    .show_ui(ui, |ui| {
+                        for operator in Operator::ALL {
+                            ui.selectable_value(&mut assertion.operator, operator, operator.text());
+                        }
+                    });
+                ui.add_enabled(
+                    assertion.operator.has_expected(),
+                    egui::TextEdit::singleline(&mut assertion.expected)
+                        .hint_text("expected")
+                        .desired_width(160.0),
+                );
+                if ui.button("del").clicked() {
+                    assertions.remove(i);
+                } else {
+                    i += 1;
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// `3/4 passed` next to the status of a response, with the results on hover.
+fn ui_assertion_summary(ui: &mut egui::Ui, results: &[AssertionResult]) {
+    if results.is_empty() {
+        return;
+    }
+    let passed = results.iter().filter(|r| r.passed).count();
+    let (color, icon) = if passed == results.len() {
+        (ADDED_COLOR, "✔")
+    } else {
+        (ui.visuals().error_fg_color, "✘")
+    };
+    ui.colored_label(color, format!("{icon} {passed}/{} passed", results.len()))
+        .on_hover_ui(|ui| ui_assertion_results(ui, results));
+}
+
+fn ui_assertion_results(ui: &mut egui::Ui, results: &[AssertionResult]) {
+    for result in results {
+        ui.horizontal(|ui| {
+            if result.passed {
+                ui.colored_label(ADDED_COLOR, format!("✔ {}", result.name));
+            } else {
+                ui.colored_label(ui.visuals().error_fg_color, format!("✘ {}", result.name));
+                match &result.actual {
+                    Some(actual) => {
+                        let short: String = actual.chars().take(80).collect();
+                        ui.weak(format!("actual: {short}")).on_hover_text(actual);
+                    }
+                    None => {
+                        ui.weak("missing");
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn ui_runner(ui: &mut egui::Ui, config: &mut RunnerConfig, run: &mut Run) {
+    let running = run.is_running();
+    ui.add_enabled_ui(!running, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("concurrency");
+            ui.add(egui::DragValue::new(&mut config.concurrency).clamp_range(1..=16));
+            ui.label("delay");
+            ui.add(
+                egui::DragValue::new(&mut config.delay)
+                    .clamp_range(0..=60_000)
+                    .suffix(" ms"),
+            );
+            ui.checkbox(&mut config.stop_on_failure, "stop on first failure");
+        });
+    });
+    ui.horizontal(|ui| {
+        if running {
+            if ui.button("Stop").clicked() {
+                run.stop();
+            }
+        } else if ui
+            .add_enabled(!run.steps.is_empty(), egui::Button::new("Run"))
+            .clicked()
+        {
+            run.start(config);
+        }
+        let (passed, failed) = run.summary();
+        if passed + failed > 0 {
+            ui.colored_label(ADDED_COLOR, format!("{passed} passed"));
+            if failed > 0 {
+                ui.colored_label(ui.visuals().error_fg_color, format!("{failed} failed"));
+            }
+        }
+    });
+    ui.separator();
+    if run.steps.is_empty() {
+        ui.weak("This directory has no requests");
+        return;
+    }
+    egui::Grid::new("runner_steps")
+        .num_columns(5)
+        .striped(true)
+        .spacing(egui::vec2(ui.spacing().item_spacing.x * 2.0, 4.0))
+        .show(ui, |ui| {
+            for heading in ["request", "state", "status", "time", "failures"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for step in &run.steps {
+                ui.label(&step.name);
+                let state = step.state.text();
+                match step.state {
+                    StepState::Passed => ui.colored_label(ADDED_COLOR, state),
+                    StepState::Failed => ui.colored_label(ui.visuals().error_fg_color, state),
+                    StepState::Running => ui.add(egui::Spinner::new()),
+                    StepState::Pending | StepState::Skipped => ui.weak(state),
+                };
+                ui.monospace(step.status.map(|s| s.to_string()).unwrap_or_default());
+                ui.monospace(step.elapsed.map(|e| format!("{e} ms")).unwrap_or_default());
+                ui.vertical(|ui| {
+                    for failure in &step.failures {
+                        ui.colored_label(ui.visuals().error_fg_color, failure);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+}
+
+/// Lines kept in the console, the oldest ones are dropped first.
+const CONSOLE_LINES: usize = 1000;
+
+fn ui_scripts(ui: &mut egui::Ui, id_source: &str, pre_request: &mut String, tests: &mut String) {
+    for (label, hint, script) in [
+        (
+            "Pre-request script",
+            "request.headers[\"X-Timestamp\"] = timestamp();",
+            pre_request,
+        ),
+        (
+            "Tests",
+            "test(\"status is 200\", response.status == 200);",
+            tests,
+        ),
+    ] {
+        ui.label(label);
+        ScrollArea::vertical()
+            .id_source((id_source, label))
+            .max_height(160.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(script)
+                        .code_editor()
+                        .hint_text(hint)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+    }
+}
+
+fn ui_console(ui: &mut egui::Ui, console: &mut Vec<LogLine>) {
+    if ui.button("clear").clicked() {
+        console.clear();
+    }
+    if console.is_empty() {
+        ui.weak("Output of print(), test() and script errors shows up here");
+        return;
+    }
+    for line in console.iter() {
+        ui.horizontal(|ui| {
+            ui.weak(&line.source);
+            match line.level {
+                LogLevel::Info => ui.monospace(&line.text),
+                LogLevel::Error => ui.colored_label(ui.visuals().error_fg_color, &line.text),
+                LogLevel::Passed => ui.colored_label(ADDED_COLOR, format!("✔ {}", line.text)),
+                LogLevel::Failed => {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("✘ {}", line.text))
+                }
+            };
+        });
+    }
+}
+
+fn ui_variables(ui: &mut egui::Ui, id_source: &str, variables: &mut Vec<(String, String)>) {
+    ui.horizontal(|ui| {
+        ui.label("Variables");
+        if ui.button("add").clicked() {
+            variables.push(("".to_owned(), "".to_owned()));
+        }
+    });
+    egui::Grid::new(id_source)
+        .num_columns(3)
+        .spacing(egui::vec2(
+            ui.spacing().item_spacing.x * 0.5,
+            ui.spacing().item_spacing.x * 0.5,
+        ))
+        .show(ui, |ui| {
+            if variables.is_empty() {
+                variables.push(("".to_owned(), "".to_owned()));
+            }
+
+            let mut i = 0;
+            while i < variables.len() {
+                ui.add(egui::TextEdit::singleline(&mut variables[i].0).hint_text("name"));
+                ui.add(egui::TextEdit::singleline(&mut variables[i].1).hint_text("value"));
+                if ui.button("del").clicked() {
+                    variables.remove(i);
+                } else {
+                    i += 1;
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// The header of a key/value table: `label`, an add button and a bulk edit toggle.
+///
+/// While bulk editing, the rows are edited as `key: value` lines below the
+/// header, see [`key_value::to_text`], and parsed back on every change, so
+/// the rows are never stale. Returns whether the table is bulk edited and
+/// whether the rows changed.
+fn ui_rows_header<R: Row + Send + Sync + 'static>(
+    ui: &mut egui::Ui,
+    id_source: &str,
+    label: &str,
+    rows: &mut Vec<R>,
+) -> (bool, bool) {
+    let id = ui.make_persistent_id((id_source, "bulk_edit"));
+    // The text and the rows when bulk editing started, whose other columns
+    // are kept by key.
+    let mut text: Option<(String, Vec<R>)> = ui.data(|d| d.get_temp(id));
+    ui.horizontal(|ui| {
+        ui.label(label);
+        if text.is_none() && ui.button("add").clicked() {
+            rows.push(R::default());
+        }
+        let mut bulk = text.is_some();
+        let toggle = ui
+            .toggle_value(&mut bulk, "Bulk edit")
+            .on_hover_text("Edit as `key: value` lines, `//` disables a line");
+        if toggle.clicked() {
+            text = match text {
+                Some(_) => None,
+                None => Some((key_value::to_text(rows), rows.clone())),
+            };
+        }
+    });
+    let bulk = text.is_some();
+    let mut changed = false;
+    match text {
+        Some((mut edited, original)) => {
+            changed = ui
+                .add(
+                    egui::TextEdit::multiline(&mut edited)
+                        .code_editor()
+                        .hint_text("Content-Type: application/json\n//X-Disabled: 1")
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY),
+                )
+                .changed();
+            if changed {
+                *rows = key_value::from_text(&edited, &original);
+            }
+            ui.data_mut(|d| d.insert_temp(id, (edited, original)));
+        }
+        None => ui.data_mut(|d| d.remove::<(String, Vec<R>)>(id)),
+    }
+    (bulk, changed)
+}
+
+/// A key/value table with an enabled toggle and a description per row, see
+/// [`ui_rows_header`].
+///
+/// Returns whether a row changed.
+fn ui_key_values<R: Row + Send + Sync + 'static>(
+    ui: &mut egui::Ui,
+    id_source: &str,
+    label: &str,
+    rows: &mut Vec<R>,
+) -> bool {
+    let (bulk, mut changed) = ui_rows_header(ui, id_source, label, rows);
+    if bulk {
+        return changed;
+    }
+    egui::Grid::new(id_source)
+        .num_columns(5)
+        .spacing(egui::vec2(
+            ui.spacing().item_spacing.x * 0.5,
+            ui.spacing().item_spacing.x * 0.5,
+        ))
+        .show(ui, |ui| {
+            if rows.is_empty() {
+                rows.push(R::default());
+            }
+
+            let mut i = 0;
+            while i < rows.len() {
+                let row = &mut rows[i];
+                let (enabled, key, value, description) = row.columns();
+                changed |= ui
+                    .add(toggle_switch(enabled))
+                    .on_hover_text("Send this row")
+                    .changed();
+                changed |= ui
+                    .add(egui::TextEdit::singleline(key).hint_text("key"))
+                    .changed();
+                let value_changed = ui
+                    .add(
+                        egui::TextEdit::singleline(value)
+                            .hint_text("value")
+                            .desired_width(300.0),
+                    )
+                    .changed();
+                ui.add(
+                    egui::TextEdit::singleline(description)
+                        .hint_text("description")
+                        .desired_width(200.0),
+                );
+                if value_changed {
+                    row.value_edited();
+                    changed = true;
+                }
+                if ui.button("del").clicked() {
+                    rows.remove(i);
+                    changed = true;
+                } else {
+                    i += 1;
+                }
+                ui.end_row();
+            }
+        });
+    changed
+}
+
+fn ui_form_data(ui: &mut egui::Ui, form_data: &mut Vec<FormPart>) {
+    let (bulk, _) = ui_rows_header(ui, "request_form_data", "Request Body", form_data);
+    if bulk {
+        return;
+    }
+    egui::Grid::new("request_form_data")
+        .num_columns(7)
+        .spacing(egui::vec2(
+            ui.spacing().item_spacing.x * 0.5,
+            ui.spacing().item_spacing.x * 0.5,
+        ))
+        .show(ui, |ui| {
+            if form_data.is_empty() {
+                form_data.push(FormPart::default());
+            }
+
+            let mut i = 0;
+            while i < form_data.len() {
+                let part = &mut form_data[i];
+                ui.add(toggle_switch(&mut part.enabled))
+                    .on_hover_text("Send this row");
+                egui::ComboBox::from_id_source(("form_data_kind", i))
+                    .width(60.0)
+                    .selected_text(format!("{:?}", part.kind))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut part.kind, FormPartKind::Text, "Text");
+                        ui.selectable_value(&mut part.kind, FormPartKind::File, "File");
+                    });
+                ui.add(egui::TextEdit::singleline(&mut part.key).hint_text("key"));
+                match part.kind {
+                    FormPartKind::Text => {
+                        ui.add(egui::TextEdit::singleline(&mut part.value).hint_text("value"));
+                    }
+                    FormPartKind::File => {
+                        ui.horizontal(|ui| {
+                            let name = std::path::Path::new(&part.value).file_name().map_or_else(
+                                || "Select file...".to_owned(),
+                                |f| f.to_string_lossy().into_owned(),
+                            );
+                            if ui.button(name).on_hover_text(&part.value).clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                    part.value = path.display().to_string();
+                                }
+                            }
+                        });
+                    }
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut part.content_type)
+                        .hint_text("content type")
+                        .desired_width(140.0),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut part.description)
+                        .hint_text("description")
+                        .desired_width(200.0),
+                );
+                if ui.button("del").clicked() {
+                    form_data.remove(i);
+                } else {
+                    i += 1;
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// `auth` is the effective auth of `location`.
+fn curl_command(location: &Location, auth: &Auth) -> String {
+    let mut url = query::with_params(&location.url, &location.params);
+    if let Some((key, value)) = auth.query() {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let pair: String = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair(&key, &value)
+            .finish();
+        url = format!("{url}{separator}{pair}");
+    }
+    let mut curl = format!("curl '{}'", url);
+    let headers = key_value::enabled_pairs(&location.header);
+
+    if location.content_type == ContentType::FormUrlEncoded {
+        key_value::enabled_pairs(&location.form_params)
+            .iter()
+            .filter(|f| !f.1.is_empty())
+            .for_each(|h| {
+                curl = format!("{} -d '{}={}'", curl, h.0, h.1);
+            });
+    }
+
+    if location.content_type == ContentType::FormData {
+        for part in location
+            .form_data
+            .iter()
+            .filter(|p| p.enabled && !p.key.is_empty())
+        {
+            let mut field = match part.kind {
+                FormPartKind::Text => format!("{}={}", part.key, part.value),
+                FormPartKind::File => format!("{}=@{}", part.key, part.value),
+            };
+            if !part.content_type.is_empty() {
+                field = format!("{};type={}", field, part.content_type);
+            }
+            // `-F` would read text starting with `@` or `<` from a file.
+            let flag = if part.kind == FormPartKind::Text
+                && (part.value.starts_with('@') || part.value.starts_with('<'))
+            {
+                "--form-string"
+            } else {
+                "-F"
+            };
+            curl = format!("{} {} '{}'", curl, flag, field);
+        }
+    }
+
+    if location.method != Method::Get {
+        curl = format!("{} -X '{}'", curl, location.method.to_text());
+    }
+
+    headers.iter().filter(|f| !f.1.is_empty()).for_each(|h| {
+        curl = format!("{} -H '{}:{}'", curl, h.0, h.1);
+    });
+
+    // Like when sending, a header set by hand wins.
+    let overridden = auth
+        .header()
+        .is_some_and(|(name, _)| headers.iter().any(|h| h.0.eq_ignore_ascii_case(&name)));
+    let options = auth.curl_options();
+    if !overridden && !options.is_empty() {
+        curl = format!("{} {}", curl, options);
+    }
+
+    let has_content_type = headers
+        .iter()
+        .any(|h| h.0.eq_ignore_ascii_case("Content-Type"));
+    if location.content_type == ContentType::Json && !location.body.is_empty() {
+        if !has_content_type {
+            curl = format!("{} -H 'Content-Type: application/json'", curl);
+        }
+        curl = format!("{} -d {}", curl, shell_quote(&location.body));
+    }
+
+    if location.content_type == ContentType::Raw && !location.body.is_empty() {
+        if !has_content_type {
+            curl = format!(
+                "{} -H 'Content-Type: {}'",
+                curl,
+                location.body_content_type()
+            );
+        }
+        curl = format!("{} --data-raw {}", curl, shell_quote(&location.body));
+    }
+
+    if location.content_type == ContentType::Binary && !location.binary_file.is_empty() {
+        if !has_content_type {
+            curl = format!(
+                "{} -H 'Content-Type: {}'",
+                curl,
+                multipart::guess_content_type(&location.binary_file)
+            );
+        }
+        curl = format!(
+            "{} --data-binary {}",
+            curl,
+            shell_quote(&format!("@{}", location.binary_file))
+        );
+    }
+
+    curl
+}
+
+/// `text` in single quotes for a POSIX shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Edit an auth config, `inherit` names what inheriting resolves to.
+fn ui_auth(ui: &mut egui::Ui, id_source: &str, auth: &mut Auth, inherit: &str) {
+    let text = |kind: AuthKind| match kind {
+        AuthKind::Inherit => format!("inherit ({inherit})"),
+        kind => kind.text().to_owned(),
+    };
+    egui::Grid::new(id_source).num_columns(2).show(ui, |ui| {
+        ui.label("Type");
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(text(auth.kind))
+            .show_ui(ui, |ui| {
+                for kind in [
+                    AuthKind::Inherit,
+                    AuthKind::None,
+                    AuthKind::Basic,
+                    AuthKind::Bearer,
+                    AuthKind::ApiKey,
+                    AuthKind::Digest,
+                    AuthKind::OAuth2,
+                    AuthKind::AwsSigV4,
+                ] {
+                    ui.selectable_value(&mut auth.kind, kind, text(kind));
+                }
+            });
+        ui.end_row();
+        match auth.kind {
+            AuthKind::Inherit | AuthKind::None => {}
+            AuthKind::Basic | AuthKind::Digest => {
+                ui.label("Username");
+                ui.text_edit_singleline(&mut auth.username);
+                ui.end_row();
+                ui.label("Password");
+                ui.add(egui::TextEdit::singleline(&mut auth.password).password(true));
+                ui.end_row();
+            }
+            AuthKind::Bearer => {
+                ui.label("Token");
+                ui.add(egui::TextEdit::singleline(&mut auth.token).desired_width(400.0));
+                ui.end_row();
+            }
+            AuthKind::ApiKey => {
+                ui.label("Key");
+                ui.add(egui::TextEdit::singleline(&mut auth.key).hint_text("X-API-Key"));
+                ui.end_row();
+                ui.label("Value");
+                ui.text_edit_singleline(&mut auth.value);
+                ui.end_row();
+                ui.label("Add to");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut auth.add_to, ApiKeyIn::Header, "header");
+                    ui.radio_value(&mut auth.add_to, ApiKeyIn::Query, "query params");
+                });
+                ui.end_row();
+            }
+            AuthKind::OAuth2 => ui_oauth2(ui, id_source, &mut auth.oauth2),
+            AuthKind::AwsSigV4 => {
+                let aws = &mut auth.aws;
+                ui.label("Access key");
+                ui.text_edit_singleline(&mut aws.access_key);
+                ui.end_row();
+                ui.label("Secret key");
+                ui.add(egui::TextEdit::singleline(&mut aws.secret_key).password(true));
+                ui.end_row();
+                ui.label("Session token");
+                ui.add(
+                    egui::TextEdit::singleline(&mut aws.session_token)
+                        .hint_text("optional")
+                        .password(true),
+                );
+                ui.end_row();
+                ui.label("Region");
+                ui.add(egui::TextEdit::singleline(&mut aws.region).hint_text("us-east-1"));
+                ui.end_row();
+                ui.label("Service");
+                ui.add(egui::TextEdit::singleline(&mut aws.service).hint_text("execute-api, s3"));
+                ui.end_row();
+            }
+        }
+    });
+    if auth.kind == AuthKind::Digest {
+        ui.weak("Sent after the server answers with a Digest challenge");
+    }
+}
+
+/// Grid rows editing an OAuth 2.0 config.
+fn ui_oauth2(ui: &mut egui::Ui, id_source: &str, config: &mut OAuth2Config) {
+    ui.label("Grant type");
+    egui::ComboBox::from_id_source((id_source, "grant"))
+        .selected_text(config.grant.text())
+        .show_ui(ui, |ui| {
+            for grant in [
+                GrantType::ClientCredentials,
+                GrantType::Password,
+                GrantType::AuthorizationCode,
+            ] {
+                ui.selectable_value(&mut config.grant, grant, grant.text());
+            }
+        });
+    ui.end_row();
+    if config.grant == GrantType::AuthorizationCode {
+        ui.label("Authorization URL");
+        ui.add(
+            egui::TextEdit::singleline(&mut config.auth_url)
+                .hint_text("https://example.org/oauth/authorize")
+                .desired_width(400.0),
+        );
+        ui.end_row();
+    }
+    ui.label("Token URL");
+    ui.add(
+        egui::TextEdit::singleline(&mut config.token_url)
+            .hint_text("https://example.org/oauth/token")
+            .desired_width(400.0),
+    );
+    ui.end_row();
+    ui.label("Client ID");
+    ui.text_edit_singleline(&mut config.client_id);
+    ui.end_row();
+    ui.label("Client secret");
+    ui.add(egui::TextEdit::singleline(&mut config.client_secret).password(true));
+    ui.end_row();
+    ui.label("Scope");
+    ui.text_edit_singleline(&mut config.scope);
+    ui.end_row();
+    match config.grant {
+        GrantType::Password => {
+            ui.label("Username");
+            ui.text_edit_singleline(&mut config.username);
+            ui.end_row();
+            ui.label("Password");
+            ui.add(egui::TextEdit::singleline(&mut config.password).password(true));
+            ui.end_row();
+        }
+        GrantType::AuthorizationCode => {
+            ui.label("Redirect port");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut config.redirect_port));
+                ui.weak(format!(
+                    "http://127.0.0.1:{}/callback, 0 picks a free port",
+                    config.redirect_port
+                ));
+            });
+            ui.end_row();
+        }
+        GrantType::ClientCredentials => {}
+    }
+    ui.label("Client authentication");
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut config.basic_auth, true, "Basic auth header");
+        ui.radio_value(&mut config.basic_auth, false, "in the body");
+    });
+    ui.end_row();
+}
+
+/// The cached token of `config`, with a button to forget it.
+fn ui_oauth_token(ui: &mut egui::Ui, tokens: &mut TokenCache, config: &OAuth2Config) {
+    let Some(token) = tokens.get(config) else {
+        ui.weak("No token yet, one is requested with the next request");
+        return;
+    };
+    let status = match token.expires_in() {
+        _ if token.is_expired() && token.refresh_token.is_empty() => "expired".to_owned(),
+        _ if token.is_expired() => "expired, refreshed with the next request".to_owned(),
+        Some(seconds) => format!("expires in {seconds} s"),
+        None => "does not expire".to_owned(),
+    };
+    let scope = token.scope.clone();
+    ui.horizontal(|ui| {
+        ui.label(format!("Token {status}"));
+        if !scope.is_empty() {
+            ui.weak(format!("scope: {scope}"));
+        }
+        if ui.button("Forget token").clicked() {
+            tokens.remove(config);
+        }
+    });
+}
+
+/// Per request overrides of the app [`Settings`].
+fn ui_request_settings(ui: &mut egui::Ui, location: &mut Location, settings: &Settings) {
+    egui::Grid::new("request_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Follow redirects");
+            let default = if settings.follow_redirects {
+                "Default (yes)"
+            } else {
+                "Default (no)"
+            };
+            let selected = match location.follow_redirects {
+                None => default,
+                Some(true) => "Yes",
+                Some(false) => "No",
+            };
+            egui::ComboBox::from_id_source("follow_redirects")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut location.follow_redirects, None, default);
+                    ui.selectable_value(&mut location.follow_redirects, Some(true), "Yes");
+                    ui.selectable_value(&mut location.follow_redirects, Some(false), "No");
+                });
+            ui.end_row();
+
+            ui.label("Max redirects");
+            ui.horizontal(|ui| {
+                let mut custom = location.max_redirects.is_some();
+                if ui.checkbox(&mut custom, "custom").changed() {
+                    location.max_redirects = custom.then_some(settings.max_redirects);
+                }
+                match &mut location.max_redirects {
+                    Some(max) => {
+                        ui.add(egui::DragValue::new(max).clamp_range(0..=100));
+                    }
+                    None => {
+                        ui.weak(settings.max_redirects.to_string());
+                    }
+                }
+            });
+            ui.end_row();
+        });
+}
+
+fn ui_failure(ui: &mut egui::Ui, failure: &Failure) {
+    let error_color = ui.visuals().error_fg_color;
+    ui.monospace(
+        egui::RichText::new(format!("error:        {}", failure.kind.text())).color(error_color),
+    );
+    ui.monospace(format!("message:      {}", failure.message));
+    ui.monospace(format!("time:         {} ms", failure.elapsed));
+    if !failure.redirects.is_empty() {
+        ui_redirects(ui, &failure.redirects);
+    }
+    ui.separator();
+}
+
+/// Waterfall of the request phases, each bar starts where the previous one ended.
+fn ui_timings(ui: &mut egui::Ui, timings: &Timings) {
+    let phases = timings.phases();
+    let total = timings.total().as_secs_f32();
+    if total <= 0.0 {
+        return;
+    }
+    let colors = [
+        Color32::from_rgb(150, 150, 150),
+        Color32::from_rgb(0, 150, 136),
+        Color32::from_rgb(255, 152, 0),
+        Color32::from_rgb(156, 39, 176),
+        Color32::from_rgb(76, 175, 80),
+        Color32::from_rgb(33, 150, 243),
+    ];
+    egui::CollapsingHeader::new("Timing")
+        .default_open(false)
+        .show(ui, |ui| {
+            egui::Grid::new("timings").num_columns(3).show(ui, |ui| {
+                let width = 300.0;
+                let mut offset = 0.0;
+                for (i, (name, duration)) in phases.iter().enumerate() {
+                    let duration = duration.as_secs_f32();
+                    ui.monospace(*name);
+                    let (rect, _) = ui.allocate_exact_size(
+                        egui::vec2(width, ui.text_style_height(&egui::TextStyle::Monospace)),
+                        egui::Sense::hover(),
+                    );
+                    let left = rect.left() + width * offset / total;
+                    let right = left + (width * duration / total).max(1.0);
+                    let bar = egui::Rect::from_x_y_ranges(left..=right, rect.y_range())
+                        .shrink2(egui::vec2(0.0, 2.0));
+                    ui.painter().rect_filled(bar, 2.0, colors[i % colors.len()]);
+                    ui.monospace(format!("{:.1} ms", duration * 1000.0));
+                    ui.end_row();
+                    offset += duration;
+                }
+                ui.monospace("total");
+                ui.label("");
+                ui.monospace(format!("{:.1} ms", total * 1000.0));
+                ui.end_row();
+            });
+            if timings.connect.is_none() {
+                ui.weak("The TCP connect of plain HTTP requests is part of the wait.");
+            }
+        });
+}
+
+/// The redirect chain, one hop per row.
+fn ui_redirects(ui: &mut egui::Ui, redirects: &[Redirect]) {
+    egui::CollapsingHeader::new(format!("Redirects ({})", redirects.len()))
+        .default_open(true)
+        .show(ui, |ui| {
+            egui::Grid::new("redirects")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    for redirect in redirects {
+                        ui.monospace(redirect.status.to_string());
+                        ui.monospace(&redirect.url);
+                        ui.monospace(format!("→ {}", redirect.location));
+                        ui.monospace(format!("{} ms", redirect.elapsed));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// The negotiated TLS session and the certificate chain of the server.
+fn ui_tls_info(ui: &mut egui::Ui, tls: &TlsInfo) {
+    egui::CollapsingHeader::new(format!("TLS ({})", tls.version))
+        .default_open(tls.insecure)
+        .show(ui, |ui| {
+            if tls.insecure {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "⚠ The server certificate was not verified",
+                );
+            }
+            ui.monospace(format!("version:      {}", tls.version));
+            ui.monospace(format!("cipher suite: {}", tls.cipher_suite));
+            egui::Grid::new("peer_certificates")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("subject");
+                    ui.strong("issuer");
+                    ui.strong("valid");
+                    ui.strong("serial");
+                    ui.end_row();
+                    for cert in &tls.peer_certificates {
+                        ui.monospace(&cert.subject);
+                        ui.monospace(&cert.issuer);
+                        ui.monospace(format!("{} – {}", cert.not_before, cert.not_after));
+                        ui.monospace(&cert.serial);
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+fn ui_resource(ui: &mut egui::Ui, resource: &Option<Resource>, results: &[AssertionResult]) {
+    if let Some(resource) = resource {
+        ui.monospace(format!("url:          {}", resource.url));
+        ui.horizontal(|ui| {
+            ui.monospace(format!(
+                "status:       {} ({})",
+                resource.status, resource.status_text
+            ));
+            ui_assertion_summary(ui, results);
+        });
+        ui.monospace(format!("content-type: {}", resource.content_type));
+        ui.monospace(format!("size:         {}", format_size(resource.length)));
+        ui.monospace(format!("time:         {} ms", resource.elapsed));
+        ui.monospace(format!(
+            "proxy:        {}",
+            resource.proxy.as_deref().unwrap_or("none")
+        ));
+        if !resource.redirects.is_empty() {
+            ui_redirects(ui, &resource.redirects);
+        }
+        ui_timings(ui, &resource.timings);
+        if let Some(tls) = &resource.tls {
+            ui_tls_info(ui, tls);
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if !results.is_empty() {
+                    egui::CollapsingHeader::new("Tests")
+                        .default_open(results.iter().any(|r| !r.passed))
+                        .show(ui, |ui| ui_assertion_results(ui, results));
+                }
+                egui::CollapsingHeader::new("Response headers")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        egui::Grid::new("response_headers")
+                            .spacing(egui::vec2(ui.spacing().item_spacing.x * 2.0, 0.0))
+                            .show(ui, |ui| {
+                                for (key, value) in &resource.headers {
+                                    ui.label(key);
+                                    ui.label(value);
+                                    ui.end_row();
+                                }
+                            })
+                    });
 
-                ui.horizontal(|ui| {
-                    let tooltip = "Click to copy the response body";
-                    if ui.button("📋").on_hover_text(tooltip).clicked() {
-                        ui.output_mut(|u| u.copied_text = body.clone());
-                    }
-                });
+                ui.separator();
+
+                if resource.bytes().is_empty() {
+                    return;
+                }
+                if resource.is_binary() {
+                    ui_binary_body(ui, resource);
+                    return;
+                }
+                if resource.spill_path.is_some() || resource.body.len() > HIGHLIGHT_LIMIT {
+                    ui_paged_body(ui, resource);
+                    return;
+                }
+                let body = match serde_json::from_str::<Value>(&resource.body) {
+                    Ok(json) => serde_json::to_string_pretty(&json).unwrap(),
+                    Err(_) => resource.body.clone(),
+                };
+                let colored_text = syntax_highlighting(ui.ctx(), &body);
+
+                ui.horizontal(|ui| {
+                    let tooltip = "Click to copy the response body";
+                    if ui.button("📋").on_hover_text(tooltip).clicked() {
+                        ui.output_mut(|u| u.copied_text = body.clone());
+                    }
+                    ui_save_body(ui, resource);
+                });
                 ui.separator();
 
                 if let Some(colored_text) = colored_text {
@@ -1259,6 +3874,165 @@ fn ui_resource(ui: &mut egui::Ui, resource: &Option<Resource>) {
     }
 }
 
+/// Bodies larger than this are shown page by page, without pretty printing or highlighting.
+const HIGHLIGHT_LIMIT: usize = 256 * 1024;
+
+/// Size of a page of a large body.
+const PAGE_SIZE: usize = 64 * 1024;
+
+/// Show one page of a large text body at a time, read from its temp file if spilled.
+fn ui_paged_body(ui: &mut egui::Ui, resource: &Resource) {
+    let id = egui::Id::new((
+        "response_page",
+        &resource.url,
+        resource.elapsed,
+        resource.length,
+    ));
+    let pages = resource.page_count(PAGE_SIZE).max(1);
+    let mut page = ui
+        .data(|d| d.get_temp::<usize>(id))
+        .unwrap_or(0)
+        .min(pages - 1);
+
+    ui.horizontal(|ui| {
+        ui_save_body(ui, resource);
+        if ui.add_enabled(page > 0, egui::Button::new("◀")).clicked() {
+            page -= 1;
+        }
+        ui.label(format!("page {} of {pages}", page + 1));
+        if ui
+            .add_enabled(page + 1 < pages, egui::Button::new("▶"))
+            .clicked()
+        {
+            page += 1;
+        }
+        if let Some(path) = &resource.spill_path {
+            ui.weak(format!("full body in {path}"));
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, page));
+    ui.separator();
+
+    let page_id = id.with(page);
+    let text = match ui.data(|d| d.get_temp::<Arc<String>>(page_id)) {
+        Some(text) => text,
+        None => {
+            let text = match resource.page(page, PAGE_SIZE) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(err) => format!("Cannot read the response body: {err}"),
+            };
+            let text = Arc::new(text);
+            ui.data_mut(|d| d.insert_temp(page_id, text.clone()));
+            text
+        }
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::vertical()
+        .id_source("paged_body")
+        .auto_shrink([false; 2])
+        .show_rows(ui, row_height, lines.len(), |ui, rows| {
+            for line in &lines[rows] {
+                ui.monospace(*line);
+            }
+        });
+}
+
+/// Images are previewed, anything else is shown as a hex dump.
+fn ui_binary_body(ui: &mut egui::Ui, resource: &Resource) {
+    ui.horizontal(|ui| {
+        ui.label(format!("{} bytes of binary data", resource.length));
+        ui_save_body(ui, resource);
+        if let Some(path) = &resource.spill_path {
+            ui.weak(format!(
+                "showing the first {}, full body in {path}",
+                format_size(resource.binary.len())
+            ));
+        }
+    });
+    ui.separator();
+
+    // A spilled image is cut off, it cannot be decoded.
+    if resource.content_type.starts_with("image/") && resource.spill_path.is_none() {
+        if let Some(texture) = response_texture(ui, resource) {
+            ui.add(egui::Image::new(&texture).shrink_to_fit());
+            return;
+        }
+        ui.colored_label(ui.visuals().warn_fg_color, "Cannot decode the image");
+    }
+
+    // Only the visible lines are formatted, so big bodies stay responsive.
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    let lines = hex::line_count(resource.binary.len());
+    egui::ScrollArea::vertical()
+        .id_source("hex_dump")
+        .auto_shrink([false; 2])
+        .show_rows(ui, row_height, lines, |ui, rows| {
+            for row in rows {
+                ui.monospace(hex::hex_line(&resource.binary, row * hex::BYTES_PER_LINE));
+            }
+        });
+}
+
+/// Decode an image response once and keep the texture for later frames.
+///
+/// `ui` only keeps the texture of the response it shows last, which frees the
+/// ones of earlier responses. A failed decode is kept as `None`.
+fn response_texture(ui: &egui::Ui, resource: &Resource) -> Option<egui::TextureHandle> {
+    let id = ui.id().with("response_image");
+    let response = egui::Id::new((&resource.url, resource.elapsed, resource.length));
+    let cached = ui.ctx().memory_mut(|m| {
+        m.data
+            .get_temp::<(egui::Id, Option<egui::TextureHandle>)>(id)
+    });
+    if let Some((decoded, texture)) = cached {
+        if decoded == response {
+            return texture;
+        }
+    }
+    let texture = image::load_from_memory(&resource.binary).ok().map(|image| {
+        let image = image.to_rgba8();
+        let size = [image.width() as usize, image.height() as usize];
+        let image =
+            egui::ColorImage::from_rgba_unmultiplied(size, image.as_flat_samples().as_slice());
+        ui.ctx()
+            .load_texture(resource.url.clone(), image, Default::default())
+    });
+    ui.ctx()
+        .memory_mut(|m| m.data.insert_temp(id, (response, texture.clone())));
+    texture
+}
+
+fn ui_save_body(ui: &mut egui::Ui, resource: &Resource) {
+    if !ui
+        .button("💾")
+        .on_hover_text("Save the response body to a file")
+        .clicked()
+    {
+        return;
+    }
+    let file_name = resource
+        .url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("response");
+    if let Some(path) = rfd::FileDialog::new().set_file_name(file_name).save_file() {
+        let saved = match &resource.spill_path {
+            Some(spill_path) => std::fs::copy(spill_path, &path).map(|_| ()),
+            None => std::fs::write(&path, resource.bytes()),
+        };
+        if let Err(err) = saved {
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_title("Save response")
+                .set_description(format!("Failed to save {}: {err}", path.display()))
+                .show();
+        }
+    }
+}
+
 fn selectable_text(ui: &mut egui::Ui, mut text: &str) {
     ui.add(
         egui::TextEdit::multiline(&mut text)
@@ -1394,6 +4168,8 @@ fn file_menu(ui: &mut egui::Ui, pending_commands: &mut Vec<Command>) {
     Command::AddApi.menu_button_ui(ui, pending_commands);
     Command::DelApi.menu_button_ui(ui, pending_commands);
     Command::RenameApi.menu_button_ui(ui, pending_commands);
+    Command::EditVariables.menu_button_ui(ui, pending_commands);
+    Command::RunDirectory.menu_button_ui(ui, pending_commands);
 }
 
 #[cfg(not(target_arch = "wasm32"))]
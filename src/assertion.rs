@@ -0,0 +1,338 @@
+//! Declarative checks of a response, edited in the Tests tab of a request.
+//!
+//! An assertion compares a part of the response, e.g. the status, a header or
+//! the value at a JSON path like `$.data.items[0].id`, with an expected value.
+//! Numbers are compared as numbers, everything else as text. Assertions are
+//! evaluated after every response, see [`evaluate`].
+
+use serde_json::Value;
+
+use crate::executor::Resource;
+
+/// The part of a response an assertion looks at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum Target {
+    #[default]
+    Status,
+    /// The header named by [`Assertion::property`], case insensitive.
+    Header,
+    /// The value at the path in [`Assertion::property`] of a JSON body.
+    JsonPath,
+    Body,
+    /// Milliseconds until the response.
+    Time,
+    /// Size of the body in bytes.
+    Size,
+}
+
+impl Target {
+    pub const ALL: [Target; 6] = [
+        Target::Status,
+        Target::Header,
+        Target::JsonPath,
+        Target::Body,
+        Target::Time,
+        Target::Size,
+    ];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Target::Status => "status",
+            Target::Header => "header",
+            Target::JsonPath => "JSON path",
+            Target::Body => "body",
+            Target::Time => "time (ms)",
+            Target::Size => "size (bytes)",
+        }
+    }
+
+    /// Whether [`Assertion::property`] names the header or path.
+    pub fn has_property(self) -> bool {
+        matches!(self, Target::Header | Target::JsonPath)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum Operator {
+    #[default]
+    Equals,
+    NotEquals,
+    Contains,
+    NotContains,
+    LessThan,
+    GreaterThan,
+    Exists,
+    NotExists,
+}
+
+impl Operator {
+    pub const ALL: [Operator; 8] = [
+        Operator::Equals,
+        Operator::NotEquals,
+        Operator::Contains,
+        Operator::NotContains,
+        Operator::LessThan,
+        Operator::GreaterThan,
+        Operator::Exists,
+        Operator::NotExists,
+    ];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Operator::Equals => "==",
+            Operator::NotEquals => "!=",
+            Operator::Contains => "contains",
+            Operator::NotContains => "not contains",
+            Operator::LessThan => "<",
+            Operator::GreaterThan => ">",
+            Operator::Exists => "exists",
+            Operator::NotExists => "not exists",
+        }
+    }
+
+    /// Whether [`Assertion::expected`] is compared with.
+    pub fn has_expected(self) -> bool {
+        !matches!(self, Operator::Exists | Operator::NotExists)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Assertion {
+    pub enabled: bool,
+    pub target: Target,
+    /// The header name or JSON path, see [`Target::has_property`].
+    pub property: String,
+    pub operator: Operator,
+    pub expected: String,
+}
+
+impl Default for Assertion {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target: Target::Status,
+            property: String::new(),
+            operator: Operator::Equals,
+            expected: String::new(),
+        }
+    }
+}
+
+/// The outcome of an [`Assertion`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct AssertionResult {
+    /// The assertion as text, e.g. `header Content-Type contains json`.
+    pub name: String,
+    pub passed: bool,
+    /// The value found in the response, `None` if it is missing.
+    pub actual: Option<String>,
+}
+
+impl Assertion {
+    /// A copy with `resolve` applied to the property and the expected value.
+    pub fn resolved(&self, resolve: impl Fn(&str) -> String) -> Assertion {
+        Assertion {
+            property: resolve(&self.property),
+            expected: resolve(&self.expected),
+            ..self.clone()
+        }
+    }
+
+    /// The assertion as text, e.g. `$.data.id exists`.
+    pub fn name(&self) -> String {
+        let mut name = match self.target {
+            Target::JsonPath => self.property.clone(),
+            Target::Header => format!("header {}", self.property),
+            target => target.text().to_owned(),
+        };
+        name.push(' ');
+        name.push_str(self.operator.text());
+        if self.operator.has_expected() {
+            name.push(' ');
+            name.push_str(&self.expected);
+        }
+        name
+    }
+
+    /// The value of the target in `resource`, `None` if it is missing.
+    ///
+    /// Bodies larger than the in-memory limit are only checked up to it.
+    fn actual(&self, resource: &Resource) -> Option<String> {
+        match self.target {
+            Target::Status => Some(resource.status.to_string()),
+            Target::Header => {
+                let values: Vec<&str> = resource
+                    .headers
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case(self.property.trim()))
+                    .map(|(_, value)| value.as_str())
+                    .collect();
+                (!values.is_empty()).then(|| values.join(", "))
+            }
+            Target::JsonPath => {
+                let json: Value = serde_json::from_str(&resource.body).ok()?;
+                json_path(&json, &self.property).map(|value| match value {
+                    Value::String(text) => text.clone(),
+                    value => value.to_string(),
+                })
+            }
+            Target::Body => Some(resource.body.clone()),
+            Target::Time => Some(resource.elapsed.to_string()),
+            Target::Size => Some(resource.length.to_string()),
+        }
+    }
+
+    pub fn evaluate(&self, resource: &Resource) -> AssertionResult {
+        let actual = self.actual(resource);
+        let expected = self.expected.trim();
+        let number = |text: &str| text.trim().parse::<f64>().ok();
+        let passed = match (self.operator, actual.as_deref()) {
+            (Operator::Exists, actual) => actual.is_some(),
+            (Operator::NotExists, actual) => actual.is_none(),
+            (_, None) => false,
+            (Operator::Equals, Some(actual)) => equals(actual, expected),
+            (Operator::NotEquals, Some(actual)) => !equals(actual, expected),
+            (Operator::Contains, Some(actual)) => actual.contains(expected),
+            (Operator::NotContains, Some(actual)) => !actual.contains(expected),
+            (Operator::LessThan, Some(actual)) => {
+                matches!((number(actual), number(expected)), (Some(a), Some(e)) if a < e)
+            }
+            (Operator::GreaterThan, Some(actual)) => {
+                matches!((number(actual), number(expected)), (Some(a), Some(e)) if a > e)
+            }
+        };
+        AssertionResult {
+            name: self.name(),
+            passed,
+            actual,
+        }
+    }
+}
+
+/// Equal as numbers if both are numbers, as text otherwise.
+fn equals(actual: &str, expected: &str) -> bool {
+    match (actual.trim().parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(actual), Ok(expected)) => actual == expected,
+        _ => actual == expected,
+    }
+}
+
+/// Evaluate the enabled `assertions` against `resource`.
+pub fn evaluate(assertions: &[Assertion], resource: &Resource) -> Vec<AssertionResult> {
+    assertions
+        .iter()
+        .filter(|assertion| assertion.enabled)
+        .map(|assertion| assertion.evaluate(resource))
+        .collect()
+}
+
+/// The value at `path` in `json`, like `$.items[0].name` or `$["a b"]`.
+///
+/// The leading `$` is optional, `None` if the path does not exist or does not parse.
+pub fn json_path<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut value = json;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some(quoted) = after.strip_prefix('"') {
+                // A quoted key may contain `]`, find its closing quote first.
+                let mut escaped = false;
+                let close = quoted.find(|c| {
+                    let end = !escaped && c == '"';
+                    escaped = !escaped && c == '\\';
+                    end
+                })?;
+                let key: String = serde_json::from_str(&after[..close + 2]).ok()?;
+                value = value.get(key)?;
+                rest = quoted[close + 1..].strip_prefix(']')?;
+            } else {
+                let end = after.find(']')?;
+                value = value.get(after[..end].trim().parse::<usize>().ok()?)?;
+                rest = &after[end + 1..];
+            }
+        } else {
+            let after = rest.strip_prefix('.').unwrap_or(rest);
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return None;
+            }
+            value = value.get(&after[..end])?;
+            rest = &after[end..];
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_path() {
+        let json: Value = serde_json::from_str(
+            r#"{"data": {"id": 7, "items": [{"name": "a"}, {"name": "b"}]}, "a b": {"]": true}}"#,
+        )
+        .unwrap();
+        assert_eq!(json_path(&json, "$.data.id"), Some(&Value::from(7)));
+        assert_eq!(
+            json_path(&json, "data.items[1].name"),
+            Some(&Value::from("b"))
+        );
+        assert_eq!(
+            json_path(&json, r#"$["a b"]["]"]"#),
+            Some(&Value::from(true))
+        );
+        assert_eq!(json_path(&json, "$"), Some(&json));
+        assert_eq!(json_path(&json, "$.data.items[2]"), None);
+        assert_eq!(json_path(&json, "$.data.missing"), None);
+        assert_eq!(json_path(&json, "$.data..id"), None);
+        assert_eq!(json_path(&json, "$.data.items[x]"), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let resource = Resource {
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: r#"{"data": {"id": 7, "name": "seven"}}"#.to_owned(),
+            elapsed: 120,
+            ..Default::default()
+        };
+        let assertion = |target, property: &str, operator, expected: &str| Assertion {
+            target,
+            property: property.to_owned(),
+            operator,
+            expected: expected.to_owned(),
+            ..Default::default()
+        };
+        let assertions = [
+            assertion(Target::Status, "", Operator::Equals, "200"),
+            assertion(Target::Header, "content-type", Operator::Contains, "json"),
+            assertion(Target::JsonPath, "$.data.id", Operator::Exists, ""),
+            assertion(Target::JsonPath, "$.data.id", Operator::Equals, "7.0"),
+            assertion(Target::JsonPath, "$.data.name", Operator::Equals, "seven"),
+            assertion(Target::Time, "", Operator::LessThan, "500"),
+            assertion(Target::JsonPath, "$.data.missing", Operator::NotExists, ""),
+        ];
+        let results = evaluate(&assertions, &resource);
+        assert!(results.iter().all(|r| r.passed), "{results:?}");
+        assert_eq!(results[1].name, "header content-type contains json");
+        assert_eq!(results[1].actual.as_deref(), Some("application/json"));
+
+        let failing = [
+            assertion(Target::Status, "", Operator::Equals, "201"),
+            assertion(Target::Header, "ETag", Operator::NotContains, "x"),
+            assertion(Target::JsonPath, "$.data.name", Operator::GreaterThan, "1"),
+            Assertion {
+                enabled: false,
+                ..Default::default()
+            },
+        ];
+        let results = evaluate(&failing, &resource);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| !r.passed), "{results:?}");
+        assert_eq!(results[1].actual, None);
+    }
+}
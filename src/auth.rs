@@ -0,0 +1,347 @@
+//! Authentication helpers: Basic, Bearer, API key and HTTP Digest, see
+//! [`crate::oauth`] for OAuth 2.0 and [`crate::aws`] for AWS Signature V4.
+//!
+//! An [`Auth`] is set on a request or on its directory, the request inherits
+//! the one of its directory by default.
+
+use base64::Engine as _;
+use md5::Md5;
+use sha2::{Digest as _, Sha256};
+
+use crate::aws::AwsSigV4;
+use crate::executor::PreparedRequest;
+use crate::hex;
+use crate::oauth::OAuth2Config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum AuthKind {
+    /// Use the auth of the directory.
+    #[default]
+    Inherit,
+    None,
+    Basic,
+    Bearer,
+    ApiKey,
+    /// HTTP Digest, answered after the `401` challenge of the server.
+    Digest,
+    /// OAuth 2.0 access token, requested before the request is sent.
+    OAuth2,
+    /// AWS Signature V4, signed by the executor right before sending.
+    AwsSigV4,
+}
+
+impl AuthKind {
+    pub fn text(self) -> &'static str {
+        match self {
+            AuthKind::Inherit => "inherit",
+            AuthKind::None => "no auth",
+            AuthKind::Basic => "Basic",
+            AuthKind::Bearer => "Bearer token",
+            AuthKind::ApiKey => "API key",
+            AuthKind::Digest => "Digest",
+            AuthKind::OAuth2 => "OAuth 2.0",
+            AuthKind::AwsSigV4 => "AWS Signature V4",
+        }
+    }
+}
+
+/// Where an API key is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum ApiKeyIn {
+    #[default]
+    Header,
+    Query,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Auth {
+    pub kind: AuthKind,
+    /// Basic and Digest.
+    pub username: String,
+    pub password: String,
+    /// Bearer.
+    pub token: String,
+    /// API key header or query parameter name.
+    pub key: String,
+    pub value: String,
+    pub add_to: ApiKeyIn,
+    pub oauth2: OAuth2Config,
+    pub aws: AwsSigV4,
+}
+
+/// Username and password answering a Digest challenge.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Auth {
+    /// The first auth that does not inherit, from the most to the least specific.
+    pub fn effective<'a>(auths: impl IntoIterator<Item = &'a Auth>) -> Auth {
+        auths
+            .into_iter()
+            .find(|a| a.kind != AuthKind::Inherit)
+            .cloned()
+            .unwrap_or(Auth {
+                kind: AuthKind::None,
+                ..Default::default()
+            })
+    }
+
+    /// A copy with `resolve` applied to every field, e.g. to replace variables.
+    pub fn resolved(&self, resolve: impl Fn(&str) -> String) -> Auth {
+        Auth {
+            username: resolve(&self.username),
+            password: resolve(&self.password),
+            token: resolve(&self.token),
+            key: resolve(&self.key),
+            value: resolve(&self.value),
+            oauth2: self.oauth2.resolved(&resolve),
+            aws: self.aws.resolved(&resolve),
+            ..self.clone()
+        }
+    }
+
+    /// The header sent for Basic, Bearer and API key auth.
+    pub fn header(&self) -> Option<(String, String)> {
+        match self.kind {
+            AuthKind::Basic => {
+                let credentials = format!("{}:{}", self.username, self.password);
+                let token = base64::engine::general_purpose::STANDARD.encode(credentials);
+                Some(("Authorization".to_owned(), format!("Basic {token}")))
+            }
+            AuthKind::Bearer => {
+                Some(("Authorization".to_owned(), format!("Bearer {}", self.token)))
+            }
+            AuthKind::ApiKey if self.add_to == ApiKeyIn::Header && !self.key.is_empty() => {
+                Some((self.key.clone(), self.value.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The query parameter sent for API key auth.
+    pub fn query(&self) -> Option<(String, String)> {
+        (self.kind == AuthKind::ApiKey && self.add_to == ApiKeyIn::Query && !self.key.is_empty())
+            .then(|| (self.key.clone(), self.value.clone()))
+    }
+
+    /// Add the auth to `request`, a header set by hand wins.
+    pub fn apply(&self, request: &mut PreparedRequest) {
+        if let Some((name, value)) = self.header() {
+            if request.header(&name).is_none() {
+                request.auth_headers.push(name.clone());
+                request.headers.push((name, value));
+            }
+        }
+        if let Some(pair) = self.query() {
+            request.query.push(pair);
+        }
+        if self.kind == AuthKind::Digest {
+            request.digest = Some(Credentials {
+                username: self.username.clone(),
+                password: self.password.clone(),
+            });
+        }
+        if self.kind == AuthKind::AwsSigV4 {
+            request.aws = Some(self.aws.clone());
+        }
+    }
+
+    /// curl options sending this auth, see [`Auth::query`] for API keys in the query.
+    pub fn curl_options(&self) -> String {
+        let user = format!("-u '{}:{}'", self.username, self.password);
+        match self.kind {
+            AuthKind::Basic => user,
+            AuthKind::Digest => format!("--digest {user}"),
+            AuthKind::AwsSigV4 => self.aws.curl_options(),
+            _ => match self.header() {
+                Some((name, value)) => format!("-H '{name}: {value}'"),
+                None => String::new(),
+            },
+        }
+    }
+}
+
+/// The `Authorization` header answering a `WWW-Authenticate: Digest ...` challenge.
+///
+/// `uri` is the request target, e.g. `/dir/index.html?a=1`, and `cnonce` a random
+/// client nonce. Returns `None` for other challenges and unsupported algorithms.
+pub fn digest_authorization(
+    challenge: &str,
+    credentials: &Credentials,
+    method: &str,
+    uri: &str,
+    cnonce: &str,
+) -> Option<String> {
+    let (scheme, params) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Digest") {
+        return None;
+    }
+    let params = parse_params(params);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+    let realm = param("realm").unwrap_or_default();
+    let nonce = param("nonce")?;
+    let algorithm = param("algorithm").unwrap_or("MD5");
+    let hash: fn(&str) -> String = match algorithm.to_uppercase().trim_end_matches("-SESS") {
+        "MD5" => |data| hex::encode(&Md5::digest(data)),
+        "SHA-256" => |data| hex::encode(&Sha256::digest(data)),
+        _ => return None,
+    };
+    // Only `auth` is supported, `auth-int` would hash the body.
+    let qop = param("qop").map(|qop| qop.split(',').any(|q| q.trim() == "auth"));
+    if qop == Some(false) {
+        return None;
+    }
+    let nc = "00000001";
+
+    let mut ha1 = hash(&format!(
+        "{}:{realm}:{}",
+        credentials.username, credentials.password
+    ));
+    if algorithm.to_uppercase().ends_with("-SESS") {
+        ha1 = hash(&format!("{ha1}:{nonce}:{cnonce}"));
+    }
+    let ha2 = hash(&format!("{method}:{uri}"));
+    let response = match qop {
+        Some(_) => hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}")),
+        None => hash(&format!("{ha1}:{nonce}:{ha2}")),
+    };
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{realm}\", nonce=\"{nonce}\", uri=\"{uri}\", \
+         algorithm={algorithm}, response=\"{response}\"",
+        credentials.username
+    );
+    if qop.is_some() {
+        header.push_str(&format!(", qop=auth, nc={nc}, cnonce=\"{cnonce}\""));
+    }
+    if let Some(opaque) = param("opaque") {
+        header.push_str(&format!(", opaque=\"{opaque}\""));
+    }
+    Some(header)
+}
+
+/// `key=value` pairs separated by commas, values may be quoted.
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_owned();
+        let value = value.trim_start();
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => {
+                let end = value.find(',').unwrap_or(value.len());
+                (value[..end].trim(), &value[end..])
+            }
+        };
+        pairs.push((key, value.to_owned()));
+        rest = next.trim_start().trim_start_matches(',');
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth() {
+        let directory = Auth {
+            kind: AuthKind::Basic,
+            username: "{{user}}".to_owned(),
+            password: "open sesame".to_owned(),
+            ..Default::default()
+        };
+        let auth = Auth::effective([&Auth::default(), &directory]);
+        let auth = auth.resolved(|s| s.replace("{{user}}", "Aladdin"));
+        assert_eq!(
+            auth.header().unwrap().1,
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+        assert_eq!(auth.curl_options(), "-u 'Aladdin:open sesame'");
+        assert_eq!(Auth::effective([]).kind, AuthKind::None);
+
+        let key = Auth {
+            kind: AuthKind::ApiKey,
+            key: "api_key".to_owned(),
+            value: "k1".to_owned(),
+            add_to: ApiKeyIn::Query,
+            ..Default::default()
+        };
+        let mut request = PreparedRequest::default();
+        key.apply(&mut request);
+        assert_eq!(request.query, vec![("api_key".to_owned(), "k1".to_owned())]);
+        assert!(request.headers.is_empty());
+
+        let bearer = Auth {
+            kind: AuthKind::Bearer,
+            token: "t0k".to_owned(),
+            ..Default::default()
+        };
+        let mut request = PreparedRequest {
+            headers: vec![("authorization".to_owned(), "own".to_owned())],
+            ..Default::default()
+        };
+        bearer.apply(&mut request);
+        assert_eq!(request.headers.len(), 1);
+        assert!(request.auth_headers.is_empty());
+        assert_eq!(bearer.curl_options(), "-H 'Authorization: Bearer t0k'");
+    }
+
+    /// The examples of RFC 7616 section 3.9.1.
+    #[test]
+    fn test_digest() {
+        let credentials = Credentials {
+            username: "Mufasa".to_owned(),
+            password: "Circle of Life".to_owned(),
+        };
+        let challenge = |algorithm: &str| {
+            format!(
+                "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+                 algorithm={algorithm}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                 opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+            )
+        };
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        let md5 = digest_authorization(
+            &challenge("MD5"),
+            &credentials,
+            "GET",
+            "/dir/index.html",
+            cnonce,
+        )
+        .unwrap();
+        assert!(md5.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+        assert!(md5.contains("opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""));
+        assert!(md5.contains("qop=auth, nc=00000001"));
+
+        let sha256 = digest_authorization(
+            &challenge("SHA-256"),
+            &credentials,
+            "GET",
+            "/dir/index.html",
+            cnonce,
+        )
+        .unwrap();
+        assert!(sha256.contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+
+        assert_eq!(
+            digest_authorization("Basic realm=\"x\"", &credentials, "GET", "/", cnonce),
+            None
+        );
+    }
+}
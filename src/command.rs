@@ -10,6 +10,9 @@ pub enum Command {
     AddApi,
     DelApi,
     RenameApi,
+    EditVariables,
+    RunDirectory,
+    CancelRequest,
 }
 
 impl Command {
@@ -26,6 +29,9 @@ impl Command {
             Command::AddApi => ("add", "add api"),
             Command::DelApi => ("del", "del api"),
             Command::RenameApi => ("rename", "rename api"),
+            Command::EditVariables => ("variables", "edit directory variables"),
+            Command::RunDirectory => ("run", "run every request of the directory"),
+            Command::CancelRequest => ("cancel", "cancel the running request"),
         }
     }
 
@@ -51,6 +57,9 @@ impl Command {
             Command::AddApi => Some(cmd(Key::A)),
             Command::DelApi => Some(cmd(Key::D)),
             Command::RenameApi => Some(cmd(Key::R)),
+            Command::EditVariables => None,
+            Command::RunDirectory => None,
+            Command::CancelRequest => Some(key(Key::Escape)),
         }
     }
 
@@ -0,0 +1,263 @@
+//! A cookie jar following the storage model of RFC 6265.
+//!
+//! Every collection has a [`CookieJar`], the cookies set by its responses are
+//! sent with its later requests by domain and path. Public suffixes are not
+//! checked, a response may set cookies for e.g. `co.uk`.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lower case, without a leading dot.
+    pub domain: String,
+    /// Only sent to `domain` itself, not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    /// Seconds since the unix epoch, `None` for session cookies.
+    pub expires: Option<u64>,
+    /// Only sent over HTTPS.
+    pub secure: bool,
+    pub http_only: bool,
+    /// `Strict`, `Lax`, `None` or empty, only shown.
+    pub same_site: String,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header received from `url`.
+    ///
+    /// Returns `None` for malformed cookies and domains `url` may not set cookies for.
+    pub fn parse(set_cookie: &str, url: &url::Url) -> Option<Cookie> {
+        let host = url.host_str()?.trim_matches(['[', ']']).to_lowercase();
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let mut cookie = Cookie {
+            name: name.trim().to_owned(),
+            value: value.trim().trim_matches('"').to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path()),
+            ..Default::default()
+        };
+        if cookie.name.is_empty() {
+            return None;
+        }
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "expires" => {
+                    cookie.expires = httpdate::parse_http_date(value)
+                        .ok()
+                        .map(|time| unix_seconds(time).max(1));
+                }
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+                    if !domain.is_empty() {
+                        if !domain_match(&host, &domain) {
+                            return None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_owned(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => cookie.same_site = value.to_owned(),
+                _ => {}
+            }
+        }
+        // Max-Age wins over Expires, zero or less removes the cookie.
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(match u64::try_from(max_age) {
+                Ok(seconds) if seconds > 0 => now().saturating_add(seconds),
+                _ => 0,
+            });
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= now())
+    }
+
+    /// The expiry as HTTP date, or `session`.
+    pub fn expires_text(&self) -> String {
+        match self.expires {
+            Some(expires) => httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(expires)),
+            None => "session".to_owned(),
+        }
+    }
+
+    /// Whether the cookie is sent with a request to `url`.
+    pub fn matches(&self, url: &url::Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.trim_matches(['[', ']']).to_lowercase();
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_ok
+            && path_match(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && !self.is_expired()
+    }
+
+    /// Cookies with the same name, domain and path replace each other.
+    fn same(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CookieJar {
+    pub cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Store the cookie of a `Set-Cookie` header received from `url`.
+    pub fn store(&mut self, url: &str, set_cookie: &str) {
+        let Some(cookie) = url::Url::parse(url)
+            .ok()
+            .and_then(|url| Cookie::parse(set_cookie, &url))
+        else {
+            return;
+        };
+        let position = self.cookies.iter().position(|c| c.same(&cookie));
+        match position {
+            Some(i) if cookie.is_expired() => {
+                self.cookies.remove(i);
+            }
+            Some(i) => self.cookies[i] = cookie,
+            None if cookie.is_expired() => {}
+            None => self.cookies.push(cookie),
+        }
+    }
+
+    /// Store the cookies of all `Set-Cookie` `headers` of a response from `url`.
+    pub fn store_response(&mut self, url: &str, headers: &[(String, String)]) {
+        for (_, value) in headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("Set-Cookie"))
+        {
+            self.store(url, value);
+        }
+    }
+
+    /// The `Cookie` header value for a request to `url`, longer paths first.
+    pub fn header(&self, url: &str) -> Option<String> {
+        let url = url::Url::parse(url).ok()?;
+        let mut cookies: Vec<&Cookie> = self.cookies.iter().filter(|c| c.matches(&url)).collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        cookies.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn remove_expired(&mut self) {
+        self.cookies.retain(|c| !c.is_expired());
+    }
+}
+
+fn now() -> u64 {
+    unix_seconds(SystemTime::now())
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(&format!(".{domain}")) && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// The directory of the request path, see RFC 6265 section 5.1.4.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(i) => path[..i].to_owned(),
+    }
+}
+
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_jar() {
+        let mut jar = CookieJar::default();
+        jar.store(
+            "https://api.example.org/auth/login",
+            "session=abc; Path=/; HttpOnly",
+        );
+        jar.store("https://api.example.org/auth/login", "step=1");
+        jar.store(
+            "https://api.example.org/",
+            "shared=yes; Domain=.Example.org; Secure; SameSite=Lax",
+        );
+        jar.store("https://api.example.org/", "evil=1; Domain=other.org");
+        jar.store(
+            "https://api.example.org/",
+            "old=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        );
+        assert_eq!(jar.cookies.len(), 3);
+        assert_eq!(jar.cookies[1].path, "/auth");
+        assert!(jar.cookies[0].http_only);
+        assert_eq!(jar.cookies[2].domain, "example.org");
+        assert_eq!(jar.cookies[2].same_site, "Lax");
+
+        assert_eq!(
+            jar.header("https://api.example.org/auth/me").as_deref(),
+            Some("step=1; session=abc; shared=yes")
+        );
+        assert_eq!(
+            jar.header("https://api.example.org/authors").as_deref(),
+            Some("session=abc; shared=yes")
+        );
+        assert_eq!(
+            jar.header("https://www.example.org/").as_deref(),
+            Some("shared=yes")
+        );
+        assert_eq!(
+            jar.header("http://api.example.org/").as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(jar.header("https://example.com/"), None);
+
+        jar.store(
+            "https://api.example.org/",
+            "session=def; Path=/; Max-Age=60",
+        );
+        assert_eq!(jar.cookies.len(), 3);
+        assert_eq!(jar.cookies[0].value, "def");
+        assert!(jar.cookies[0].expires.is_some());
+        assert!(!jar.cookies[0].is_expired());
+
+        jar.store("https://api.example.org/", "session=; Path=/; Max-Age=0");
+        assert_eq!(jar.cookies.len(), 2);
+        assert!(jar.cookies.iter().all(|c| c.name != "session"));
+    }
+}
@@ -0,0 +1,387 @@
+//! Differences between two responses, shown by the Compare window.
+//!
+//! JSON bodies are compared structurally by path, e.g. `$.items[2].name`,
+//! other bodies line by line. Headers are compared by lower case name.
+
+use serde_json::Value;
+
+/// A difference at a JSON path or in a header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        path: String,
+        new: String,
+    },
+    Removed {
+        path: String,
+        old: String,
+    },
+    Changed {
+        path: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// A line of a line diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// The difference of two bodies.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BodyDiff {
+    /// Both bodies are JSON, with the pretty printed bodies to show side by side.
+    Json {
+        changes: Vec<Change>,
+        old: String,
+        new: String,
+    },
+    /// Rows of the old and new lines side by side, see [`side_by_side`].
+    Lines(Vec<(Option<String>, Option<String>)>),
+}
+
+/// Above this many compared line pairs, changed blocks are not aligned anymore.
+const LINE_PAIRS_LIMIT: usize = 4_000_000;
+
+/// Compare two bodies, structurally if both are JSON.
+pub fn body(old: &str, new: &str) -> BodyDiff {
+    match (
+        serde_json::from_str::<Value>(old),
+        serde_json::from_str::<Value>(new),
+    ) {
+        (Ok(old), Ok(new)) => BodyDiff::Json {
+            changes: json(&old, &new),
+            old: serde_json::to_string_pretty(&old).unwrap_or_default(),
+            new: serde_json::to_string_pretty(&new).unwrap_or_default(),
+        },
+        _ => BodyDiff::Lines(side_by_side(&lines(old, new))),
+    }
+}
+
+/// The paths added, removed or changed from `old` to `new`.
+///
+/// Arrays are compared index by index, values are shown as compact JSON.
+pub fn json(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    json_at("$", old, new, &mut changes);
+    changes
+}
+
+fn json_at(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old) in old {
+                let path = key_path(path, key);
+                match new.get(key) {
+                    Some(new) => json_at(&path, old, new, changes),
+                    None => changes.push(Change::Removed {
+                        path,
+                        old: old.to_string(),
+                    }),
+                }
+            }
+            for (key, new) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                changes.push(Change::Added {
+                    path: key_path(path, key),
+                    new: new.to_string(),
+                });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{path}[{i}]");
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => json_at(&path, old, new, changes),
+                    (Some(old), None) => changes.push(Change::Removed {
+                        path,
+                        old: old.to_string(),
+                    }),
+                    (None, Some(new)) => changes.push(Change::Added {
+                        path,
+                        new: new.to_string(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.to_owned(),
+            old: old.to_string(),
+            new: new.to_string(),
+        }),
+        _ => {}
+    }
+}
+
+/// `$.key`, or `$["some key"]` if `key` is not an identifier.
+fn key_path(path: &str, key: &str) -> String {
+    let identifier = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if identifier {
+        format!("{path}.{key}")
+    } else {
+        format!("{path}[{}]", Value::from(key))
+    }
+}
+
+/// Headers added, removed or changed, repeated headers are joined with `, `.
+pub fn headers(old: &[(String, String)], new: &[(String, String)]) -> Vec<Change> {
+    let old = header_map(old);
+    let new = header_map(new);
+    let mut changes = Vec::new();
+    for (name, old_value) in &old {
+        match new.get(name) {
+            Some(new_value) if new_value != old_value => changes.push(Change::Changed {
+                path: name.clone(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(Change::Removed {
+                path: name.clone(),
+                old: old_value.clone(),
+            }),
+        }
+    }
+    for (name, new_value) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        changes.push(Change::Added {
+            path: name.clone(),
+            new: new_value.clone(),
+        });
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+fn header_map(headers: &[(String, String)]) -> std::collections::BTreeMap<String, String> {
+    let mut map = std::collections::BTreeMap::<String, String>::new();
+    for (name, value) in headers {
+        map.entry(name.to_lowercase())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(value);
+            })
+            .or_insert_with(|| value.clone());
+    }
+    map
+}
+
+/// The lines removed from `old` and added in `new`, by longest common subsequence.
+pub fn lines(old: &str, new: &str) -> Vec<Line> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut diff: Vec<Line> = old[..prefix]
+        .iter()
+        .map(|l| Line::Same(l.to_string()))
+        .collect();
+    if a.len().saturating_mul(b.len()) > LINE_PAIRS_LIMIT {
+        diff.extend(a.iter().map(|l| Line::Removed(l.to_string())));
+        diff.extend(b.iter().map(|l| Line::Added(l.to_string())));
+    } else {
+        // common[i][j]: length of the longest common subsequence of a[i..] and b[j..].
+        let mut common = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                common[i][j] = if a[i] == b[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                diff.push(Line::Same(a[i].to_string()));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || common[i + 1][j] >= common[i][j + 1]) {
+                diff.push(Line::Removed(a[i].to_string()));
+                i += 1;
+            } else {
+                diff.push(Line::Added(b[j].to_string()));
+                j += 1;
+            }
+        }
+    }
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|l| Line::Same(l.to_string())),
+    );
+    diff
+}
+
+/// Rows of old and new lines, a block of removed lines is shown next to the
+/// lines added in its place.
+pub fn side_by_side(lines: &[Line]) -> Vec<(Option<String>, Option<String>)> {
+    let mut rows = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for line in lines {
+        match line {
+            Line::Removed(line) => removed.push(line.clone()),
+            Line::Added(line) => added.push(line.clone()),
+            Line::Same(line) => {
+                pair_up(&mut rows, &mut removed, &mut added);
+                rows.push((Some(line.clone()), Some(line.clone())));
+            }
+        }
+    }
+    pair_up(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+fn pair_up(
+    rows: &mut Vec<(Option<String>, Option<String>)>,
+    removed: &mut Vec<String>,
+    added: &mut Vec<String>,
+) {
+    let len = removed.len().max(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for _ in 0..len {
+        rows.push((removed.next(), added.next()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_diff() {
+        let old: Value = serde_json::from_str(
+            r#"{"id": 1, "tags": ["a", "b"], "user": {"name": "Ann", "mail": "a@x"}, "a b": 1}"#,
+        )
+        .unwrap();
+        let new: Value = serde_json::from_str(
+            r#"{"id": 1, "tags": ["a", "c", "d"], "user": {"name": "Ann", "age": 3}, "a b": null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            json(&old, &new),
+            vec![
+                Change::Changed {
+                    path: "$[\"a b\"]".to_owned(),
+                    old: "1".to_owned(),
+                    new: "null".to_owned()
+                },
+                Change::Changed {
+                    path: "$.tags[1]".to_owned(),
+                    old: "\"b\"".to_owned(),
+                    new: "\"c\"".to_owned()
+                },
+                Change::Added {
+                    path: "$.tags[2]".to_owned(),
+                    new: "\"d\"".to_owned()
+                },
+                Change::Removed {
+                    path: "$.user.mail".to_owned(),
+                    old: "\"a@x\"".to_owned()
+                },
+                Change::Added {
+                    path: "$.user.age".to_owned(),
+                    new: "3".to_owned()
+                },
+            ]
+        );
+        assert!(json(&old, &old).is_empty());
+        assert!(matches!(body("[1]", "[2]"), BodyDiff::Json { changes, .. } if changes.len() == 1));
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let old = "<html>\n<p>one</p>\n<p>two</p>\n</html>";
+        let new = "<html>\n<p>one</p>\n<p>2</p>\n<p>three</p>\n</html>";
+        assert_eq!(
+            lines(old, new),
+            vec![
+                Line::Same("<html>".to_owned()),
+                Line::Same("<p>one</p>".to_owned()),
+                Line::Removed("<p>two</p>".to_owned()),
+                Line::Added("<p>2</p>".to_owned()),
+                Line::Added("<p>three</p>".to_owned()),
+                Line::Same("</html>".to_owned()),
+            ]
+        );
+        let BodyDiff::Lines(rows) = body(old, new) else {
+            panic!("not JSON");
+        };
+        let some = |s: &str| Some(s.to_owned());
+        assert_eq!(rows[2], (some("<p>two</p>"), some("<p>2</p>")));
+        assert_eq!(rows[3], (None, some("<p>three</p>")));
+        assert_eq!(rows.len(), 5);
+
+        assert_eq!(
+            lines("a\nb\nc", "b\nc\nd"),
+            vec![
+                Line::Removed("a".to_owned()),
+                Line::Same("b".to_owned()),
+                Line::Same("c".to_owned()),
+                Line::Added("d".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_diff() {
+        let header = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+        let old = [
+            header("Content-Type", "text/html"),
+            header("Set-Cookie", "a=1"),
+            header("X-Version", "1"),
+        ];
+        let new = [
+            header("content-type", "text/html"),
+            header("Set-Cookie", "a=1"),
+            header("Set-Cookie", "b=2"),
+            header("Etag", "\"x\""),
+        ];
+        assert_eq!(
+            headers(&old, &new),
+            vec![
+                Change::Added {
+                    path: "etag".to_owned(),
+                    new: "\"x\"".to_owned()
+                },
+                Change::Changed {
+                    path: "set-cookie".to_owned(),
+                    old: "a=1".to_owned(),
+                    new: "a=1, b=2".to_owned()
+                },
+                Change::Removed {
+                    path: "x-version".to_owned(),
+                    old: "1".to_owned()
+                },
+            ]
+        );
+    }
+}
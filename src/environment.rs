@@ -0,0 +1,177 @@
+//! Named environments and `{{variable}}` substitution.
+
+use std::collections::BTreeMap;
+
+use crate::proxy::ProxyConfig;
+
+/// How deep values may nest placeholders that are resolved in turn.
+const MAX_RESOLVE_DEPTH: usize = 8;
+
+/// A named set of key/value variables, e.g. "dev", "staging" or "prod".
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Environment {
+    pub id: String,
+    pub name: String,
+    pub variables: Vec<(String, String)>,
+    /// Proxy used while this environment is active, e.g. only for "prod".
+    pub proxy: ProxyConfig,
+}
+
+impl Environment {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.into(),
+            variables: vec![("".to_owned(), "".to_owned())],
+            proxy: ProxyConfig::default(),
+        }
+    }
+}
+
+/// The variables visible to a single request, merged from all scopes.
+///
+/// Layers are pushed from the lowest to the highest precedence,
+/// so a later layer overrides keys of an earlier one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VariableScope {
+    variables: BTreeMap<String, String>,
+}
+
+impl VariableScope {
+    pub fn push_layer(&mut self, variables: &[(String, String)]) -> &mut Self {
+        for (key, value) in variables {
+            let key = key.trim();
+            if !key.is_empty() {
+                self.variables.insert(key.to_owned(), value.clone());
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name.trim()).map(String::as_str)
+    }
+
+    /// Replace every `{{name}}` in `text` with its value.
+    ///
+    /// Unknown variables are left untouched so they stay visible in the request,
+    /// as are variables whose value refers back to themselves, e.g. `a` in
+    /// `a = {{b}}, b = {{a}}`.
+    pub fn resolve(&self, text: &str) -> String {
+        self.resolve_in(text, &mut Vec::new())
+    }
+
+    /// Resolve `text` inside the values of the variables in `expanding`.
+    fn resolve_in<'a>(&'a self, text: &str, expanding: &mut Vec<&'a str>) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}") {
+                Some(end) => {
+                    let name = after[..end].trim();
+                    match self.variables.get_key_value(name) {
+                        Some((name, value))
+                            if !expanding.contains(&name.as_str())
+                                && expanding.len() < MAX_RESOLVE_DEPTH =>
+                        {
+                            expanding.push(name);
+                            out.push_str(&self.resolve_in(value, expanding));
+                            expanding.pop();
+                        }
+                        _ => out.push_str(&rest[start..start + 2 + end + 2]),
+                    }
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Names of all `{{placeholders}}` in `text` that `scope` cannot resolve.
+pub fn unresolved_variables(scope: &VariableScope, text: &str) -> Vec<String> {
+    let resolved = scope.resolve(text);
+    let mut names = Vec::new();
+    let mut rest = resolved.as_str();
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim().to_owned();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &after[end + 2..];
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(pairs: &[(&str, &str)]) -> VariableScope {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut scope = VariableScope::default();
+        scope.push_layer(&pairs);
+        scope
+    }
+
+    #[test]
+    fn test_resolve() {
+        let scope = scope(&[("host", "example.org"), ("id", "42")]);
+        assert_eq!(
+            scope.resolve("https://{{host}}/items/{{ id }}"),
+            "https://example.org/items/42"
+        );
+        assert_eq!(scope.resolve("{{missing}}/{{id}}"), "{{missing}}/42");
+        assert_eq!(scope.resolve("{{id"), "{{id");
+        assert_eq!(scope.resolve("no variables"), "no variables");
+    }
+
+    #[test]
+    fn test_layers_and_nesting() {
+        let mut scope = scope(&[("host", "dev.local"), ("base", "https://{{host}}")]);
+        scope.push_layer(&[("host".to_owned(), "prod.example.org".to_owned())]);
+        assert_eq!(scope.resolve("{{base}}/"), "https://prod.example.org/");
+
+        let looping = self::scope(&[("a", "{{b}}"), ("b", "{{c}}"), ("c", "<{{a}}>")]);
+        assert_eq!(looping.resolve("{{a}}"), "<{{a}}>");
+        assert_eq!(looping.resolve("{{c}}"), "<{{c}}>");
+        assert_eq!(
+            unresolved_variables(&looping, "{{b}}"),
+            vec!["b".to_owned()]
+        );
+        let own = self::scope(&[("path", "/api{{path}}")]);
+        assert_eq!(own.resolve("{{path}}/x"), "/api{{path}}/x");
+
+        let chain: Vec<(String, String)> = (0..=MAX_RESOLVE_DEPTH)
+            .map(|i| (format!("v{i}"), format!("{{{{v{}}}}}", i + 1)))
+            .collect();
+        let mut deep = VariableScope::default();
+        deep.push_layer(&chain);
+        let last = format!("{{{{v{MAX_RESOLVE_DEPTH}}}}}");
+        assert_eq!(deep.resolve("{{v0}}"), last);
+    }
+
+    #[test]
+    fn test_unresolved_variables() {
+        let scope = scope(&[("host", "example.org")]);
+        assert_eq!(
+            unresolved_variables(&scope, "{{host}}/{{token}}?{{token}}&{{ page }}"),
+            vec!["token".to_owned(), "page".to_owned()]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Hex dump of binary data, as shown by `hexdump -C`, and hex encoded digests.
+
+/// Number of bytes shown per line.
+pub const BYTES_PER_LINE: usize = 16;
+
+/// The line of the dump starting at `offset`.
+pub fn hex_line(bytes: &[u8], offset: usize) -> String {
+    let end = (offset + BYTES_PER_LINE).min(bytes.len());
+    let chunk = &bytes[offset.min(end)..end];
+
+    let mut line = format!("{offset:08x}  ");
+    for i in 0..BYTES_PER_LINE {
+        match chunk.get(i) {
+            Some(b) => line.push_str(&format!("{b:02x} ")),
+            None => line.push_str("   "),
+        }
+        if i == BYTES_PER_LINE / 2 - 1 {
+            line.push(' ');
+        }
+    }
+    line.push_str(" |");
+    line.extend(chunk.iter().map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+            b as char
+        } else {
+            '.'
+        }
+    }));
+    line.push('|');
+    line
+}
+
+/// Lower case hex digits of `bytes`, e.g. of a hash.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Number of lines needed to dump `len` bytes.
+pub fn line_count(len: usize) -> usize {
+    len.div_ceil(BYTES_PER_LINE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_line() {
+        let bytes = b"Hello, binary\x00\x01\x02world";
+        assert_eq!(line_count(bytes.len()), 2);
+        assert_eq!(encode(&bytes[..3]), "48656c");
+        assert_eq!(
+            hex_line(bytes, 0),
+            "00000000  48 65 6c 6c 6f 2c 20 62  69 6e 61 72 79 00 01 02  |Hello, binary...|"
+        );
+        assert_eq!(
+            hex_line(bytes, 16),
+            "00000010  77 6f 72 6c 64                                    |world|"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! History of the executed requests.
+//!
+//! Every finished request is appended as one JSON line to a file in the storage
+//! directory of the app, outside of its eframe state, so a response survives
+//! the next send of its tab. Only the last [`MAX_ENTRIES`] are kept, response
+//! bodies are cut after [`BODY_LIMIT`] bytes.
+
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::executor::Resource;
+
+/// Entries kept, the oldest ones are dropped first.
+pub const MAX_ENTRIES: usize = 500;
+
+/// Bytes of a response body kept in an entry.
+pub const BODY_LIMIT: usize = 256 * 1024;
+
+/// An executed request, `L` is the request as sent with its response or failure.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct HistoryEntry<L> {
+    pub id: String,
+    /// Seconds since the unix epoch.
+    pub time: u64,
+    /// Name of the environment active when the request was sent.
+    pub environment: Option<String>,
+    pub method: String,
+    pub url: String,
+    /// `None` if the request failed.
+    pub status: Option<usize>,
+    /// Milliseconds until the response or failure.
+    pub elapsed: u128,
+    pub location: L,
+}
+
+impl<L> HistoryEntry<L> {
+    /// The host of `url`, empty if it is not a valid url.
+    pub fn host(&self) -> String {
+        url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default()
+    }
+
+    /// The time sent as HTTP date.
+    pub fn time_text(&self) -> String {
+        httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(self.time))
+    }
+
+    pub fn matches(&self, filter: &Filter) -> bool {
+        let search = filter.search.to_lowercase();
+        (search.is_empty() || self.url.to_lowercase().contains(&search))
+            && filter.status.matches(self.status)
+            && (filter.method.is_empty() || filter.method == self.method)
+            && (filter.host.is_empty() || filter.host == self.host())
+    }
+}
+
+/// Status classes the history is filtered by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StatusFilter {
+    #[default]
+    Any,
+    Success,
+    Redirect,
+    ClientError,
+    ServerError,
+    Failed,
+}
+
+impl StatusFilter {
+    pub const ALL: [StatusFilter; 6] = [
+        StatusFilter::Any,
+        StatusFilter::Success,
+        StatusFilter::Redirect,
+        StatusFilter::ClientError,
+        StatusFilter::ServerError,
+        StatusFilter::Failed,
+    ];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            StatusFilter::Any => "any status",
+            StatusFilter::Success => "2xx",
+            StatusFilter::Redirect => "3xx",
+            StatusFilter::ClientError => "4xx",
+            StatusFilter::ServerError => "5xx",
+            StatusFilter::Failed => "failed",
+        }
+    }
+
+    fn matches(self, status: Option<usize>) -> bool {
+        match (self, status) {
+            (StatusFilter::Any, _) => true,
+            (StatusFilter::Failed, status) => status.is_none(),
+            (_, None) => false,
+            (StatusFilter::Success, Some(status)) => (200..300).contains(&status),
+            (StatusFilter::Redirect, Some(status)) => (300..400).contains(&status),
+            (StatusFilter::ClientError, Some(status)) => (400..500).contains(&status),
+            (StatusFilter::ServerError, Some(status)) => status >= 500,
+        }
+    }
+}
+
+/// What the History panel shows, empty fields match every entry.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Filter {
+    /// Part of the url, case insensitive.
+    pub search: String,
+    pub status: StatusFilter,
+    pub method: String,
+    pub host: String,
+}
+
+/// The executed requests, oldest first.
+pub struct History<L> {
+    entries: Vec<HistoryEntry<L>>,
+    /// The JSON lines file, `None` keeps the history in memory only.
+    path: Option<PathBuf>,
+}
+
+impl<L> Default for History<L> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            path: None,
+        }
+    }
+}
+
+/// `history.jsonl` in the storage directory of the app.
+pub fn default_path() -> Option<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return eframe::storage_dir("Reston").map(|dir| dir.join("history.jsonl"));
+    #[cfg(target_arch = "wasm32")]
+    None
+}
+
+impl<L: Serialize + DeserializeOwned> History<L> {
+    /// Read the history stored in `path`, skipping lines that do not parse.
+    ///
+    /// A missing file is an empty history, a file with more than
+    /// [`MAX_ENTRIES`] is trimmed.
+    pub fn load(path: Option<PathBuf>) -> std::io::Result<Self> {
+        let mut history = History {
+            entries: Vec::new(),
+            path,
+        };
+        let Some(path) = &history.path else {
+            return Ok(history);
+        };
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(history),
+            Err(err) => return Err(err),
+        };
+        history.entries = text
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        if history.entries.len() > MAX_ENTRIES {
+            history.trim();
+            history.rewrite()?;
+        }
+        Ok(history)
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry<L>] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&HistoryEntry<L>> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Add `entry` and append it to the file.
+    ///
+    /// The file is only trimmed by the next [`History::load`].
+    pub fn push(&mut self, entry: HistoryEntry<L>) -> std::io::Result<()> {
+        let appended = match &self.path {
+            Some(path) => append(path, &entry),
+            None => Ok(()),
+        };
+        self.entries.push(entry);
+        self.trim();
+        appended
+    }
+
+    pub fn remove(&mut self, id: &str) -> std::io::Result<()> {
+        self.entries.retain(|e| e.id != id);
+        self.rewrite()
+    }
+
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.entries.clear();
+        self.rewrite()
+    }
+
+    /// The distinct methods of the entries, sorted.
+    pub fn methods(&self) -> Vec<String> {
+        let mut methods: Vec<String> = self.entries.iter().map(|e| e.method.clone()).collect();
+        methods.sort();
+        methods.dedup();
+        methods
+    }
+
+    /// The distinct hosts of the entries, sorted.
+    pub fn hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = self.entries.iter().map(HistoryEntry::host).collect();
+        hosts.retain(|host| !host.is_empty());
+        hosts.sort();
+        hosts.dedup();
+        hosts
+    }
+
+    fn trim(&mut self) {
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    fn rewrite(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&serde_json::to_string(entry)?);
+            text.push('\n');
+        }
+        std::fs::write(path, text)
+    }
+}
+
+fn append<L: Serialize>(path: &PathBuf, entry: &HistoryEntry<L>) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// A copy of `resource` to keep in the history: the body is cut after
+/// [`BODY_LIMIT`] bytes and no longer refers to its temp file, which is
+/// deleted once the tab gets another response.
+pub fn stored_resource(resource: &Resource) -> Resource {
+    let mut body = resource.body.clone();
+    if body.len() > BODY_LIMIT {
+        let mut end = BODY_LIMIT;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    let mut binary = resource.binary.clone();
+    binary.truncate(BODY_LIMIT);
+    Resource {
+        body,
+        binary,
+        spill_path: None,
+        ..resource.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, method: &str, url: &str, status: Option<usize>) -> HistoryEntry<String> {
+        HistoryEntry {
+            id: id.to_owned(),
+            time: 784111777,
+            environment: None,
+            method: method.to_owned(),
+            url: url.to_owned(),
+            status,
+            elapsed: 12,
+            location: format!("{method} {url}"),
+        }
+    }
+
+    #[test]
+    fn test_history_file() {
+        let dir = std::env::temp_dir().join(format!("reston-history-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.jsonl");
+        let mut history = History::<String>::load(Some(path.clone())).unwrap();
+        assert!(history.entries().is_empty());
+
+        for i in 0..MAX_ENTRIES + 2 {
+            let e = entry(&i.to_string(), "GET", "https://example.org/", Some(200));
+            history.push(e).unwrap();
+        }
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0].id, "2");
+
+        // The file keeps every line until it is loaded again.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, MAX_ENTRIES + 3);
+        let mut history = History::<String>::load(Some(path.clone())).unwrap();
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0].id, "2");
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, MAX_ENTRIES);
+
+        history.remove("2").unwrap();
+        let history = History::<String>::load(Some(path.clone())).unwrap();
+        assert_eq!(history.entries()[0].id, "3");
+        assert_eq!(
+            history.get("4").unwrap().location,
+            "GET https://example.org/"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut history = History::default();
+        for e in [
+            entry(
+                "1",
+                "GET",
+                "https://api.example.org/users?page=2",
+                Some(200),
+            ),
+            entry("2", "POST", "https://api.example.org/users", Some(422)),
+            entry("3", "GET", "http://localhost:8080/health", Some(503)),
+            entry("4", "DELETE", "https://other.example.com/x", None),
+        ] {
+            history.push(e).unwrap();
+        }
+        let ids = |filter: &Filter| -> Vec<String> {
+            history
+                .entries()
+                .iter()
+                .filter(|e| e.matches(filter))
+                .map(|e| e.id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&Filter::default()).len(), 4);
+        let users = Filter {
+            search: "USERS".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(ids(&users), ["1", "2"]);
+        let errors = Filter {
+            status: StatusFilter::ClientError,
+            ..users
+        };
+        assert_eq!(ids(&errors), ["2"]);
+        let failed = Filter {
+            status: StatusFilter::Failed,
+            ..Default::default()
+        };
+        assert_eq!(ids(&failed), ["4"]);
+        let local_gets = Filter {
+            method: "GET".to_owned(),
+            host: "localhost".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(ids(&local_gets), ["3"]);
+
+        assert_eq!(history.methods(), ["DELETE", "GET", "POST"]);
+        assert_eq!(
+            history.hosts(),
+            ["api.example.org", "localhost", "other.example.com"]
+        );
+        assert_eq!(
+            history.entries()[0].time_text(),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+    }
+
+    #[test]
+    fn test_stored_resource() {
+        let resource = Resource {
+            body: "é".repeat(BODY_LIMIT),
+            spill_path: Some("/tmp/reston-x.body".to_owned()),
+            length: 3 * BODY_LIMIT,
+            ..Default::default()
+        };
+        let stored = stored_resource(&resource);
+        assert_eq!(stored.body.len(), BODY_LIMIT);
+        assert_eq!(stored.spill_path, None);
+        assert_eq!(stored.length, 3 * BODY_LIMIT);
+    }
+}
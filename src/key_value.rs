@@ -0,0 +1,256 @@
+//! Rows of the key/value tables of a request: headers and form fields.
+//!
+//! Every row can be disabled to keep it around without sending it, and
+//! carries a description that is never sent. Tables are bulk edited as
+//! `key: value` lines, see [`to_text`] and [`from_text`].
+
+/// A header or `application/x-www-form-urlencoded` field.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "KeyValueRepr")]
+pub struct KeyValue {
+    pub enabled: bool,
+    pub key: String,
+    pub value: String,
+    pub description: String,
+}
+
+impl Default for KeyValue {
+    fn default() -> Self {
+        Self::new("", "")
+    }
+}
+
+impl KeyValue {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            enabled: true,
+            key: key.into(),
+            value: value.into(),
+            description: String::new(),
+        }
+    }
+}
+
+/// Rows used to be stored as `(key, value)` pairs.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum KeyValueRepr {
+    Pair(String, String),
+    Row {
+        #[serde(default = "enabled")]
+        enabled: bool,
+        #[serde(default)]
+        key: String,
+        #[serde(default)]
+        value: String,
+        #[serde(default)]
+        description: String,
+    },
+}
+
+impl From<KeyValueRepr> for KeyValue {
+    fn from(repr: KeyValueRepr) -> Self {
+        match repr {
+            KeyValueRepr::Pair(key, value) => KeyValue::new(key, value),
+            KeyValueRepr::Row {
+                enabled,
+                key,
+                value,
+                description,
+            } => KeyValue {
+                enabled,
+                key,
+                value,
+                description,
+            },
+        }
+    }
+}
+
+/// Serde default of `enabled` flags, rows are sent unless disabled.
+pub fn enabled() -> bool {
+    true
+}
+
+/// A row of a key/value table, see `ui_key_values` in the app.
+pub trait Row: Default + Clone {
+    /// The enabled flag, key and value.
+    fn line(&self) -> (bool, &str, &str);
+
+    /// The enabled flag, key, value and description.
+    fn columns(&mut self) -> (&mut bool, &mut String, &mut String, &mut String);
+
+    /// Called after the value was edited in the table.
+    fn value_edited(&mut self) {}
+}
+
+impl Row for KeyValue {
+    fn line(&self) -> (bool, &str, &str) {
+        (self.enabled, &self.key, &self.value)
+    }
+
+    fn columns(&mut self) -> (&mut bool, &mut String, &mut String, &mut String) {
+        (
+            &mut self.enabled,
+            &mut self.key,
+            &mut self.value,
+            &mut self.description,
+        )
+    }
+}
+
+/// The `(key, value)` pairs of the enabled rows with a key, as sent.
+pub fn enabled_pairs(rows: &[KeyValue]) -> Vec<(String, String)> {
+    rows.iter()
+        .filter(|row| row.enabled && !row.key.is_empty())
+        .map(|row| (row.key.clone(), row.value.clone()))
+        .collect()
+}
+
+/// One `key: value` line per row with a key, disabled rows start with `//`.
+pub fn to_text<R: Row>(rows: &[R]) -> String {
+    let mut text = String::new();
+    for row in rows {
+        let (enabled, key, value) = row.line();
+        if key.is_empty() {
+            continue;
+        }
+        if !enabled {
+            text.push_str("//");
+        }
+        text.push_str(&format!("{key}: {value}\n"));
+    }
+    text
+}
+
+/// Parse `key: value` lines back into rows, see [`to_text`].
+///
+/// A row keeps the other columns of the first row in `rows` with the same key.
+/// Also takes headers copied from browser devtools: the request line and
+/// HTTP/2 pseudo-headers like `:authority` are skipped, and a value may follow
+/// on the line after its `name:`.
+pub fn from_text<R: Row>(text: &str, rows: &[R]) -> Vec<R> {
+    let mut used = vec![false; rows.len()];
+    let mut parsed = Vec::new();
+    let mut lines = text.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        let (enabled, line) = match line.strip_prefix("//") {
+            Some(rest) => (false, rest.trim_start()),
+            None => (true, line),
+        };
+        if line.is_empty() || line.starts_with(':') || is_request_line(line) {
+            continue;
+        }
+        let (key, mut value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim().to_owned()),
+            None => (line, String::new()),
+        };
+        if value.is_empty() && line.ends_with(':') {
+            if let Some(next) = lines.next_if(|next| !next.is_empty() && !next.contains(": ")) {
+                value = next.to_owned();
+            }
+        }
+
+        let same_key = (0..rows.len()).find(|&i| !used[i] && rows[i].line().1 == key);
+        let mut row = match same_key {
+            Some(i) => {
+                used[i] = true;
+                rows[i].clone()
+            }
+            None => R::default(),
+        };
+        let (row_enabled, row_key, row_value, _) = row.columns();
+        *row_enabled = enabled;
+        *row_key = key.to_owned();
+        if *row_value != value {
+            *row_value = value;
+            row.value_edited();
+        }
+        parsed.push(row);
+    }
+    parsed
+}
+
+/// `GET /path HTTP/1.1` at the top of raw request headers.
+fn is_request_line(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let method = words.next().unwrap_or_default();
+    method.chars().all(|c| c.is_ascii_uppercase())
+        && words.last().is_some_and(|w| w.starts_with("HTTP/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_values() {
+        let rows: Vec<KeyValue> = serde_json::from_str(
+            r#"[["Accept", "*/*"], {"key": "X-Debug", "value": "1", "enabled": false}, ["", ""]]"#,
+        )
+        .unwrap();
+        assert!(rows[0].enabled);
+        assert_eq!(rows[1].value, "1");
+        assert_eq!(
+            enabled_pairs(&rows),
+            vec![("Accept".to_owned(), "*/*".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_bulk_text() {
+        let rows = vec![
+            KeyValue {
+                description: "JSON please".to_owned(),
+                ..KeyValue::new("Accept", "application/json")
+            },
+            KeyValue {
+                enabled: false,
+                ..KeyValue::new("X-Debug", "1")
+            },
+            KeyValue::default(),
+        ];
+        let text = to_text(&rows);
+        assert_eq!(text, "Accept: application/json\n//X-Debug: 1\n");
+
+        let edited = format!("{text}\n// X-Trace: on\nAuthorization: Bearer a:b\n");
+        let parsed = from_text(&edited, &rows);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].description, "JSON please");
+        assert!(!parsed[1].enabled);
+        assert_eq!(
+            parsed[2],
+            KeyValue {
+                enabled: false,
+                ..KeyValue::new("X-Trace", "on")
+            }
+        );
+        assert_eq!(parsed[3].value, "Bearer a:b");
+    }
+
+    #[test]
+    fn test_devtools_headers() {
+        let raw = "GET /api/items?page=2 HTTP/1.1\n\
+                   Host: example.org\n\
+                   Accept: */*\n";
+        let parsed: Vec<KeyValue> = from_text(raw, &[]);
+        assert_eq!(
+            enabled_pairs(&parsed),
+            vec![
+                ("Host".to_owned(), "example.org".to_owned()),
+                ("Accept".to_owned(), "*/*".to_owned()),
+            ]
+        );
+
+        let chrome =
+            ":authority: example.org\n:method: GET\naccept:\ntext/html\nuser-agent: Mozilla/5.0\n";
+        let parsed: Vec<KeyValue> = from_text(chrome, &[]);
+        assert_eq!(
+            enabled_pairs(&parsed),
+            vec![
+                ("accept".to_owned(), "text/html".to_owned()),
+                ("user-agent".to_owned(), "Mozilla/5.0".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,5 +1,8 @@
 //! Rerun GUI theme and helpers, built around [`egui`](https://www.egui.rs/).
 mod app;
+mod assertion;
+mod auth;
+mod aws;
 pub use app::HttpApp;
 pub use crate::url_parser::*;
 
@@ -8,10 +11,27 @@ mod url_parser;
 
 mod command;
 mod command_palette;
+mod cookies;
 mod design_tokens;
+mod diff;
+mod environment;
+mod executor;
+mod hex;
+mod history;
+mod key_value;
+mod multipart;
+mod oauth;
+mod proxy;
+mod query;
+mod runner;
+mod script;
+mod timing;
+mod tls;
 pub mod egui_helpers;
 pub mod icons;
 mod static_image_cache;
+#[cfg(test)]
+mod test_server;
 pub mod toasts;
 mod toggle_switch;
 
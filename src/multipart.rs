@@ -0,0 +1,151 @@
+//! `multipart/form-data` encoding (RFC 7578).
+
+use std::path::Path;
+
+/// One field of a multipart body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub name: String,
+    pub body: PartBody,
+    /// Overrides the content type guessed for the part, if not empty.
+    pub content_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartBody {
+    Text(String),
+    /// Path of a file whose content is sent.
+    File(String),
+}
+
+/// A fresh, random boundary that is very unlikely to appear in any part.
+pub fn boundary() -> String {
+    format!("----reston{}", uuid::Uuid::new_v4().simple())
+}
+
+/// The `Content-Type` header value of a body encoded with `boundary`.
+pub fn content_type(boundary: &str) -> String {
+    format!("multipart/form-data; boundary={boundary}")
+}
+
+/// Encode `parts`, reading file parts from disk.
+pub fn encode(parts: &[Part], boundary: &str) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for part in parts {
+        out.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+        let name = escape(&part.name);
+        match &part.body {
+            PartBody::Text(text) => {
+                out.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{name}\"\r\n").as_bytes(),
+                );
+                if !part.content_type.is_empty() {
+                    out.extend_from_slice(
+                        format!("Content-Type: {}\r\n", part.content_type).as_bytes(),
+                    );
+                }
+                out.extend_from_slice(b"\r\n");
+                out.extend_from_slice(text.as_bytes());
+            }
+            PartBody::File(path) => {
+                let content = std::fs::read(path)?;
+                let filename = Path::new(path)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let content_type = if part.content_type.is_empty() {
+                    guess_content_type(path)
+                } else {
+                    &part.content_type
+                };
+                out.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; filename=\"{}\"\r\n\
+                         Content-Type: {content_type}\r\n\r\n",
+                        escape(&filename)
+                    )
+                    .as_bytes(),
+                );
+                out.extend_from_slice(&content);
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    Ok(out)
+}
+
+/// Quotes and line breaks would end the quoted name early.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Content type of a file, from its extension.
+pub fn guess_content_type(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "js" => "text/javascript",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let path = std::env::temp_dir().join(format!("reston-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"{}").unwrap();
+        let path = path.display().to_string();
+
+        let parts = vec![
+            Part {
+                name: "title".to_owned(),
+                body: PartBody::Text("hello".to_owned()),
+                content_type: "".to_owned(),
+            },
+            Part {
+                name: "upload".to_owned(),
+                body: PartBody::File(path.clone()),
+                content_type: "".to_owned(),
+            },
+        ];
+        let encoded = String::from_utf8(encode(&parts, "XX").unwrap()).unwrap();
+        let filename = Path::new(&path).file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            encoded,
+            format!(
+                "--XX\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n\
+                 --XX\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"{filename}\"\r\n\
+                 Content-Type: application/json\r\n\r\n{{}}\r\n--XX--\r\n"
+            )
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = Part {
+            name: "f".to_owned(),
+            body: PartBody::File("/nonexistent/file".to_owned()),
+            content_type: "".to_owned(),
+        };
+        assert!(encode(&[missing], "XX").is_err());
+    }
+}
@@ -0,0 +1,698 @@
+//! OAuth 2.0 access tokens: the client credentials, password and
+//! authorization code (with PKCE) grants, refreshed when they expire.
+//!
+//! Tokens are cached per collection in a [`TokenCache`], keyed by the
+//! [`OAuth2Config`] they were requested with. Token requests go through
+//! [`execute`], with the proxy and TLS options of the authorized request.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use sha2::{Digest as _, Sha256};
+
+use crate::executor::{
+    execute, CancelToken, Failure, FailureKind, Outcome, PreparedRequest, Progress, RequestBody,
+};
+
+/// Tokens are refreshed this long before they expire.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// How long to wait for the browser to come back with an authorization code.
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a connection to the redirect listener may take to send its
+/// request line, e.g. a preconnect of the browser sends nothing.
+const REQUEST_LINE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the redirect listener checks for a cancel.
+const POLL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum GrantType {
+    #[default]
+    ClientCredentials,
+    Password,
+    /// Log in with the browser, the code comes back to a loopback listener.
+    AuthorizationCode,
+}
+
+impl GrantType {
+    pub fn text(self) -> &'static str {
+        match self {
+            GrantType::ClientCredentials => "client credentials",
+            GrantType::Password => "password",
+            GrantType::AuthorizationCode => "authorization code (PKCE)",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct OAuth2Config {
+    pub grant: GrantType,
+    pub token_url: String,
+    /// Authorization endpoint of the authorization code grant.
+    pub auth_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space separated scopes.
+    pub scope: String,
+    /// Resource owner credentials of the password grant.
+    pub username: String,
+    pub password: String,
+    /// Loopback port receiving the authorization code, 0 picks a free port.
+    pub redirect_port: u16,
+    /// Send the client credentials as Basic auth instead of in the body.
+    pub basic_auth: bool,
+}
+
+impl OAuth2Config {
+    /// Identifies the tokens of this config in a [`TokenCache`].
+    pub fn cache_key(&self) -> String {
+        format!(
+            "{:?} {} {} {} {}",
+            self.grant, self.token_url, self.client_id, self.scope, self.username
+        )
+    }
+
+    /// A copy with `resolve` applied to every field, e.g. to replace variables.
+    pub fn resolved(&self, resolve: impl Fn(&str) -> String) -> OAuth2Config {
+        OAuth2Config {
+            token_url: resolve(&self.token_url),
+            auth_url: resolve(&self.auth_url),
+            client_id: resolve(&self.client_id),
+            client_secret: resolve(&self.client_secret),
+            scope: resolve(&self.scope),
+            username: resolve(&self.username),
+            password: resolve(&self.password),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Token {
+    pub access_token: String,
+    pub token_type: String,
+    /// Empty if the server did not send one.
+    pub refresh_token: String,
+    /// Seconds since the unix epoch, `None` if the token does not expire.
+    pub expires_at: Option<u64>,
+    pub scope: String,
+}
+
+impl Token {
+    /// Whether the token expires within [`EXPIRY_MARGIN`].
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= now() + EXPIRY_MARGIN.as_secs())
+    }
+
+    /// Seconds until the token expires.
+    pub fn expires_in(&self) -> Option<i64> {
+        self.expires_at
+            .map(|expires_at| expires_at as i64 - now() as i64)
+    }
+
+    /// The `Authorization` header value.
+    pub fn authorization(&self) -> String {
+        match self.token_type.as_str() {
+            t if t.is_empty() || t.eq_ignore_ascii_case("bearer") => {
+                format!("Bearer {}", self.access_token)
+            }
+            t => format!("{t} {}", self.access_token),
+        }
+    }
+}
+
+/// The tokens of a collection, see [`OAuth2Config::cache_key`].
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TokenCache {
+    pub tokens: BTreeMap<String, Token>,
+}
+
+impl TokenCache {
+    pub fn get(&self, config: &OAuth2Config) -> Option<&Token> {
+        self.tokens.get(&config.cache_key())
+    }
+
+    pub fn insert(&mut self, config: &OAuth2Config, token: Token) {
+        self.tokens.insert(config.cache_key(), token);
+    }
+
+    pub fn remove(&mut self, config: &OAuth2Config) {
+        self.tokens.remove(&config.cache_key());
+    }
+}
+
+/// Add the access token of `config` to `request`.
+///
+/// The `cached` token is used until it expires, then refreshed or requested
+/// again. Returns the new token to cache, if one was requested. `open_url`
+/// shows the login page of the authorization code grant.
+///
+/// Like the other auth kinds, an `Authorization` header set by hand wins, no
+/// token is requested then.
+pub fn authorize(
+    request: &mut PreparedRequest,
+    config: &OAuth2Config,
+    cached: Option<&Token>,
+    cancel: &CancelToken,
+    open_url: &dyn Fn(&str),
+) -> Result<Option<Token>, Failure> {
+    if request.header("Authorization").is_some() {
+        return Ok(None);
+    }
+    let start = Instant::now();
+    let fresh = match cached {
+        Some(token) if !token.is_expired() => None,
+        Some(token) if !token.refresh_token.is_empty() => {
+            // Refresh tokens expire as well, then log in again.
+            match refresh(config, token, request, cancel, start) {
+                Ok(token) => Some(token),
+                Err(failure) if failure.kind == FailureKind::Cancelled => return Err(failure),
+                Err(_) => Some(request_token(config, request, cancel, open_url, start)?),
+            }
+        }
+        _ => Some(request_token(config, request, cancel, open_url, start)?),
+    };
+    let token = fresh.as_ref().or(cached).expect("a token");
+    request
+        .headers
+        .push(("Authorization".to_owned(), token.authorization()));
+    Ok(fresh)
+}
+
+fn refresh(
+    config: &OAuth2Config,
+    token: &Token,
+    template: &PreparedRequest,
+    cancel: &CancelToken,
+    start: Instant,
+) -> Result<Token, Failure> {
+    let fields = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", &token.refresh_token),
+    ];
+    let mut fresh = token_request(config, template, fields, cancel, start)?;
+    // The server may keep the refresh token.
+    if fresh.refresh_token.is_empty() {
+        fresh.refresh_token = token.refresh_token.clone();
+    }
+    Ok(fresh)
+}
+
+fn request_token(
+    config: &OAuth2Config,
+    template: &PreparedRequest,
+    cancel: &CancelToken,
+    open_url: &dyn Fn(&str),
+    start: Instant,
+) -> Result<Token, Failure> {
+    match config.grant {
+        GrantType::ClientCredentials => {
+            let mut fields = vec![("grant_type", "client_credentials")];
+            if !config.scope.is_empty() {
+                fields.push(("scope", &config.scope));
+            }
+            token_request(config, template, fields, cancel, start)
+        }
+        GrantType::Password => {
+            let mut fields = vec![
+                ("grant_type", "password"),
+                ("username", &config.username),
+                ("password", &config.password),
+            ];
+            if !config.scope.is_empty() {
+                fields.push(("scope", &config.scope));
+            }
+            token_request(config, template, fields, cancel, start)
+        }
+        GrantType::AuthorizationCode => {
+            authorization_code(config, template, cancel, open_url, start)
+        }
+    }
+}
+
+/// Open the login page and wait for the code on a loopback redirect, see RFC 8252.
+fn authorization_code(
+    config: &OAuth2Config,
+    template: &PreparedRequest,
+    cancel: &CancelToken,
+    open_url: &dyn Fn(&str),
+    start: Instant,
+) -> Result<Token, Failure> {
+    let failure = |message: String| auth_failure(message, start);
+    let listener = TcpListener::bind(("127.0.0.1", config.redirect_port))
+        .map_err(|err| failure(format!("cannot listen for the redirect: {err}")))?;
+    let port = listener.local_addr().map(|a| a.port()).unwrap_or_default();
+    let redirect_uri = format!("http://127.0.0.1:{port}/callback");
+    let (verifier, challenge) = pkce();
+    let state = uuid::Uuid::new_v4().simple().to_string();
+
+    let mut url = url::Url::parse(&config.auth_url)
+        .map_err(|err| failure(format!("invalid authorization url: {err}")))?;
+    {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &config.client_id)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+        if !config.scope.is_empty() {
+            query.append_pair("scope", &config.scope);
+        }
+    }
+    open_url(url.as_str());
+
+    let code = wait_for_code(&listener, &state, cancel).map_err(|message| {
+        if cancel.is_cancelled() {
+            Failure::cancelled(start)
+        } else {
+            failure(message)
+        }
+    })?;
+    let fields = vec![
+        ("grant_type", "authorization_code"),
+        ("code", &code),
+        ("redirect_uri", &redirect_uri),
+        ("code_verifier", &verifier),
+    ];
+    token_request(config, template, fields, cancel, start)
+}
+
+/// A random code verifier and its S256 challenge.
+fn pkce() -> (String, String) {
+    let verifier = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let challenge =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(&verifier));
+    (verifier, challenge)
+}
+
+/// Accept redirects until one carries the code for `state`.
+fn wait_for_code(
+    listener: &TcpListener,
+    state: &str,
+    cancel: &CancelToken,
+) -> Result<String, String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + AUTHORIZATION_TIMEOUT;
+    while Instant::now() < deadline {
+        if cancel.is_cancelled() {
+            return Err("cancelled".to_owned());
+        }
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL);
+                continue;
+            }
+            Err(err) => return Err(err.to_string()),
+        };
+        let Some(line) = read_request_line(&stream, cancel, deadline) else {
+            continue;
+        };
+        let target = line.split_whitespace().nth(1).unwrap_or_default();
+        let Ok(url) = url::Url::parse(&format!("http://127.0.0.1{target}")) else {
+            continue;
+        };
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.into_owned())
+        };
+        // e.g. the browser asking for a favicon
+        if url.path() != "/callback" || param("state").as_deref() != Some(state) {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+            continue;
+        }
+        let result = match (param("code"), param("error")) {
+            (Some(code), _) => Ok(code),
+            (None, error) => Err(format!(
+                "authorization denied: {} {}",
+                error.unwrap_or_default(),
+                param("error_description").unwrap_or_default()
+            )),
+        };
+        let page = match &result {
+            Ok(_) => "Logged in, you can close this window and return to reston.",
+            Err(message) => message.as_str(),
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{page}",
+            page.len()
+        );
+        return result;
+    }
+    Err("timed out waiting for the login in the browser".to_owned())
+}
+
+/// The first line of the request on `stream`, `None` if it does not arrive
+/// within [`REQUEST_LINE_TIMEOUT`] or before `cancel` is set.
+fn read_request_line(
+    stream: &TcpStream,
+    cancel: &CancelToken,
+    deadline: Instant,
+) -> Option<String> {
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(POLL)).ok()?;
+    let give_up = deadline.min(Instant::now() + REQUEST_LINE_TIMEOUT);
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while !cancel.is_cancelled() && Instant::now() < give_up {
+        match reader.read_line(&mut line) {
+            Ok(_) => return Some(line),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Post `fields` and the client credentials to the token endpoint.
+fn token_request<'a>(
+    config: &'a OAuth2Config,
+    template: &PreparedRequest,
+    mut fields: Vec<(&'a str, &'a str)>,
+    cancel: &CancelToken,
+    start: Instant,
+) -> Result<Token, Failure> {
+    let mut headers = vec![("Accept".to_owned(), "application/json".to_owned())];
+    if config.basic_auth {
+        let credentials = format!("{}:{}", config.client_id, config.client_secret);
+        let credentials = base64::engine::general_purpose::STANDARD.encode(credentials);
+        headers.push(("Authorization".to_owned(), format!("Basic {credentials}")));
+    } else {
+        fields.push(("client_id", &config.client_id));
+        if !config.client_secret.is_empty() {
+            fields.push(("client_secret", &config.client_secret));
+        }
+    }
+    let request = PreparedRequest {
+        method: "POST".to_owned(),
+        url: config.token_url.clone(),
+        headers,
+        body: RequestBody::Form(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        ),
+        timeout: template.timeout,
+        proxy: template.proxy.clone(),
+        tls: template.tls.clone(),
+        ..Default::default()
+    };
+    match execute(&request, cancel, &Progress::default()) {
+        Outcome::Response(resource) => parse_token(resource.status, &resource.body)
+            .map_err(|message| auth_failure(message, start)),
+        Outcome::Failure(failure) if failure.kind == FailureKind::Cancelled => Err(failure),
+        Outcome::Failure(failure) => Err(auth_failure(
+            format!("token request failed: {}", failure.message),
+            start,
+        )),
+    }
+}
+
+/// Parse the JSON response of the token endpoint.
+fn parse_token(status: usize, body: &str) -> Result<Token, String> {
+    let json: serde_json::Value = serde_json::from_str(body)
+        .map_err(|_| format!("token endpoint answered {status}: {body}"))?;
+    let text = |name: &str| match &json[name] {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    };
+    if let Some(error) = json["error"].as_str() {
+        return Err(format!(
+            "token endpoint answered {status}: {error} {}",
+            text("error_description")
+        ));
+    }
+    let access_token = text("access_token");
+    if access_token.is_empty() {
+        return Err(format!(
+            "no access_token in the answer of the token endpoint: {body}"
+        ));
+    }
+    Ok(Token {
+        access_token,
+        token_type: text("token_type"),
+        refresh_token: text("refresh_token"),
+        expires_at: text("expires_in")
+            .parse::<u64>()
+            .ok()
+            .map(|seconds| now() + seconds),
+        scope: text("scope"),
+    })
+}
+
+fn auth_failure(message: String, start: Instant) -> Failure {
+    Failure {
+        kind: FailureKind::Auth,
+        message,
+        elapsed: start.elapsed().as_millis(),
+        ..Default::default()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{response, serve, TestRequest};
+    use std::sync::{Arc, Mutex};
+
+    /// The forms posted to the token endpoint.
+    type Forms = Arc<Mutex<Vec<BTreeMap<String, String>>>>;
+
+    fn form(req: &TestRequest) -> BTreeMap<String, String> {
+        url::form_urlencoded::parse(&req.body)
+            .into_owned()
+            .collect()
+    }
+
+    /// Issues `token-N` for the N-th token request, remembering every request form.
+    fn token_server(challenge: Arc<Mutex<String>>) -> (String, Forms) {
+        let forms = Arc::new(Mutex::new(Vec::new()));
+        let seen = forms.clone();
+        let base = serve(move |req| {
+            let form = form(req);
+            let mut forms = seen.lock().unwrap();
+            forms.push(form.clone());
+            let ok = match form.get("grant_type").map(String::as_str) {
+                Some("client_credentials") => {
+                    form.get("client_secret").is_some_and(|s| s == "s3cret")
+                }
+                Some("password") => form.get("password").is_some_and(|p| p == "pw"),
+                Some("refresh_token") => {
+                    form.get("refresh_token").is_some_and(|t| t == "refresh-1")
+                }
+                Some("authorization_code") => {
+                    let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+                    let expected = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                        .encode(Sha256::digest(&verifier));
+                    form.get("code").is_some_and(|c| c == "the-code")
+                        && *challenge.lock().unwrap() == expected
+                }
+                _ => false,
+            };
+            if !ok {
+                return response(
+                    "400 Bad Request",
+                    &[("Content-Type", "application/json")],
+                    br#"{"error":"invalid_grant","error_description":"nope"}"#,
+                );
+            }
+            let body = format!(
+                r#"{{"access_token":"token-{}","token_type":"bearer","expires_in":3600,"refresh_token":"refresh-1"}}"#,
+                forms.len()
+            );
+            response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                body.as_bytes(),
+            )
+        });
+        (format!("{base}/token"), forms)
+    }
+
+    #[test]
+    fn test_client_credentials_and_refresh() {
+        let (token_url, forms) = token_server(Default::default());
+        let config = OAuth2Config {
+            token_url,
+            client_id: "app".to_owned(),
+            client_secret: "s3cret".to_owned(),
+            scope: "read write".to_owned(),
+            ..Default::default()
+        };
+        let cancel = CancelToken::default();
+        let no_browser = |_: &str| panic!("no browser needed");
+
+        let mut request = PreparedRequest::default();
+        let token = authorize(&mut request, &config, None, &cancel, &no_browser)
+            .unwrap()
+            .expect("a new token");
+        assert_eq!(token.access_token, "token-1");
+        assert!(!token.is_expired());
+        assert_eq!(request.header("Authorization"), Some("Bearer token-1"));
+        assert_eq!(forms.lock().unwrap()[0]["scope"], "read write");
+
+        // A valid cached token is reused.
+        let mut request = PreparedRequest::default();
+        let fresh = authorize(&mut request, &config, Some(&token), &cancel, &no_browser).unwrap();
+        assert_eq!(fresh, None);
+        assert_eq!(request.header("Authorization"), Some("Bearer token-1"));
+        assert_eq!(forms.lock().unwrap().len(), 1);
+
+        // A header set by hand is kept.
+        let mut request = PreparedRequest {
+            headers: vec![("authorization".to_owned(), "own".to_owned())],
+            ..Default::default()
+        };
+        let fresh = authorize(&mut request, &config, None, &cancel, &no_browser).unwrap();
+        assert_eq!(fresh, None);
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(request.header("Authorization"), Some("own"));
+        assert_eq!(forms.lock().unwrap().len(), 1);
+
+        // An expired one is refreshed.
+        let expired = Token {
+            expires_at: Some(now()),
+            ..token
+        };
+        let mut request = PreparedRequest::default();
+        let token = authorize(&mut request, &config, Some(&expired), &cancel, &no_browser)
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.access_token, "token-2");
+        assert_eq!(forms.lock().unwrap()[1]["grant_type"], "refresh_token");
+
+        // A rejected refresh token falls back to the grant.
+        let stale = Token {
+            refresh_token: "revoked".to_owned(),
+            ..expired
+        };
+        let mut request = PreparedRequest::default();
+        let token = authorize(&mut request, &config, Some(&stale), &cancel, &no_browser)
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.access_token, "token-4");
+
+        let wrong = OAuth2Config {
+            client_secret: "wrong".to_owned(),
+            ..config
+        };
+        let mut request = PreparedRequest::default();
+        let failure = authorize(&mut request, &wrong, None, &cancel, &no_browser).unwrap_err();
+        assert_eq!(failure.kind, FailureKind::Auth);
+        assert!(failure.message.contains("invalid_grant nope"));
+    }
+
+    #[test]
+    fn test_password_grant() {
+        let (token_url, forms) = token_server(Default::default());
+        let config = OAuth2Config {
+            grant: GrantType::Password,
+            token_url,
+            client_id: "app".to_owned(),
+            username: "me".to_owned(),
+            password: "pw".to_owned(),
+            basic_auth: true,
+            ..Default::default()
+        };
+        let mut request = PreparedRequest::default();
+        let token = authorize(
+            &mut request,
+            &config,
+            None,
+            &CancelToken::default(),
+            &|_| {},
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(token.access_token, "token-1");
+        let forms = forms.lock().unwrap();
+        assert_eq!(forms[0]["username"], "me");
+        assert!(!forms[0].contains_key("client_id"));
+    }
+
+    #[test]
+    fn test_authorization_code() {
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let (token_url, forms) = token_server(challenge.clone());
+        let config = OAuth2Config {
+            grant: GrantType::AuthorizationCode,
+            token_url,
+            auth_url: "https://login.example.org/authorize?prompt=login".to_owned(),
+            client_id: "app".to_owned(),
+            ..Default::default()
+        };
+        // Stands in for the browser: log in and follow the redirect.
+        let browser = |url: &str| {
+            let url = url::Url::parse(url).unwrap();
+            let param = |name: &str| {
+                url.query_pairs()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.into_owned())
+                    .unwrap()
+            };
+            assert_eq!(param("prompt"), "login");
+            assert_eq!(param("code_challenge_method"), "S256");
+            *challenge.lock().unwrap() = param("code_challenge");
+            let callback = format!(
+                "{}?code=the-code&state={}",
+                param("redirect_uri"),
+                param("state")
+            );
+            std::thread::spawn(move || {
+                // A preconnect that never sends a request.
+                let addr = url::Url::parse(&callback)
+                    .unwrap()
+                    .socket_addrs(|| None)
+                    .unwrap();
+                let _idle = std::net::TcpStream::connect(&addr[..]).unwrap();
+                let favicon = callback.replace("/callback?", "/favicon.ico?");
+                let ignored = ureq::get(&favicon).call();
+                assert!(matches!(ignored, Err(ureq::Error::Status(404, _))));
+                ureq::get(&callback).call().unwrap();
+            });
+        };
+        let mut request = PreparedRequest::default();
+        let token = authorize(
+            &mut request,
+            &config,
+            None,
+            &CancelToken::default(),
+            &browser,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(token.access_token, "token-1");
+        assert_eq!(forms.lock().unwrap()[0]["grant_type"], "authorization_code");
+    }
+}
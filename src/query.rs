@@ -0,0 +1,337 @@
+//! Query parameters of a request url.
+//!
+//! The Params table of a `Location` is the source of truth, the query of its
+//! url is rewritten from the table and parsed back into it when the url is
+//! edited. Parsing never fails, text that is not a valid escape is kept as is.
+//! Disabled params are kept in the table only.
+
+use crate::key_value::{self, Row};
+
+/// A decoded query parameter.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "ParamRepr")]
+pub struct Param {
+    pub enabled: bool,
+    pub key: String,
+    pub value: String,
+    /// Sent as `?key` instead of `?key=`, only while `value` is empty.
+    pub key_only: bool,
+    pub description: String,
+}
+
+impl Default for Param {
+    fn default() -> Self {
+        Self::new("", "")
+    }
+}
+
+impl Param {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            enabled: true,
+            key: key.into(),
+            value: value.into(),
+            key_only: false,
+            description: String::new(),
+        }
+    }
+
+    fn encode(&self) -> String {
+        if self.key_only && self.value.is_empty() {
+            encode_component(&self.key)
+        } else {
+            format!(
+                "{}={}",
+                encode_component(&self.key),
+                encode_component(&self.value)
+            )
+        }
+    }
+}
+
+/// Params used to be stored as `(key, value)` pairs.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum ParamRepr {
+    Pair(String, String),
+    Param {
+        #[serde(default = "key_value::enabled")]
+        enabled: bool,
+        #[serde(default)]
+        key: String,
+        #[serde(default)]
+        value: String,
+        #[serde(default)]
+        key_only: bool,
+        #[serde(default)]
+        description: String,
+    },
+}
+
+impl From<ParamRepr> for Param {
+    fn from(repr: ParamRepr) -> Self {
+        match repr {
+            ParamRepr::Pair(key, value) => Param::new(key, value),
+            ParamRepr::Param {
+                enabled,
+                key,
+                value,
+                key_only,
+                description,
+            } => Param {
+                enabled,
+                key,
+                value,
+                key_only,
+                description,
+            },
+        }
+    }
+}
+
+impl Row for Param {
+    fn line(&self) -> (bool, &str, &str) {
+        (self.enabled, &self.key, &self.value)
+    }
+
+    fn columns(&mut self) -> (&mut bool, &mut String, &mut String, &mut String) {
+        (
+            &mut self.enabled,
+            &mut self.key,
+            &mut self.value,
+            &mut self.description,
+        )
+    }
+
+    fn value_edited(&mut self) {
+        self.key_only = false;
+    }
+}
+
+/// The query of `url` without the `?`, if it has one.
+pub fn query(url: &str) -> Option<&str> {
+    let (_, query, _) = split(url);
+    query
+}
+
+/// The params of the query of `url`.
+pub fn params(url: &str) -> Vec<Param> {
+    query(url).map(parse).unwrap_or_default()
+}
+
+/// The params of `url` after it was edited, keeping the disabled `rows`, the
+/// rows without key and the descriptions of the rows whose key did not change.
+///
+/// The kept rows are not in the url, see [`encode`], so syncing twice or with
+/// an unchanged url changes nothing.
+pub fn sync(url: &str, rows: &[Param]) -> Vec<Param> {
+    let mut parsed = params(url).into_iter();
+    let mut synced = Vec::new();
+    for row in rows {
+        if !row.enabled || row.key.is_empty() {
+            synced.push(row.clone());
+        } else if let Some(mut param) = parsed.next() {
+            if param.key == row.key {
+                param.description = row.description.clone();
+            }
+            synced.push(param);
+        }
+    }
+    synced.extend(parsed);
+    synced
+}
+
+/// Decode a query like `a=1&b=x%20y&flag`, skipping empty pairs.
+pub fn parse(query: &str) -> Vec<Param> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => Param::new(decode_component(key), decode_component(value)),
+            None => Param {
+                key_only: true,
+                ..Param::new(decode_component(pair), "")
+            },
+        })
+        .collect()
+}
+
+/// Encode `params` as query without the `?`, disabled rows and rows without
+/// key are skipped.
+pub fn encode(params: &[Param]) -> String {
+    params
+        .iter()
+        .filter(|p| p.enabled && !p.key.is_empty())
+        .map(Param::encode)
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `url` with its query replaced by `params`, keeping its fragment.
+pub fn with_params(url: &str, params: &[Param]) -> String {
+    let (base, _, fragment) = split(url);
+    let query = encode(params);
+    if query.is_empty() {
+        format!("{base}{fragment}")
+    } else {
+        format!("{base}?{query}{fragment}")
+    }
+}
+
+/// Split `url` in the part before the query, the query and the `#fragment`.
+fn split(url: &str) -> (&str, Option<&str>, &str) {
+    let (rest, fragment) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    match rest.split_once('?') {
+        Some((base, query)) => (base, Some(query), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+/// Percent-encode what would change the meaning of the query.
+///
+/// `{{variables}}` and the other printable characters allowed in a query stay
+/// readable, a space becomes `%20`.
+fn encode_component(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' => encoded.push(c),
+            '-' | '_' | '.' | '~' | '!' | '$' | '\'' | '(' | ')' | '*' | ',' | ';' | ':' | '@'
+            | '/' | '?' | '{' | '}' | '[' | ']' | '|' => encoded.push(c),
+            c => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    encoded.push_str(&format!("%{b:02X}"));
+                }
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode `%XX` escapes and `+` as space, keeping text that is not valid UTF-8 as is.
+fn decode_component(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(b), _) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (None, b'+') => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (None, b) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| text.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let url =
+            "https://{{host}}/search?q=rust+egui&tag=a&tag=b&empty=&flag&name=J%C3%BCrgen%26co#top";
+        let params = params(url);
+        assert_eq!(
+            params,
+            vec![
+                Param::new("q", "rust egui"),
+                Param::new("tag", "a"),
+                Param::new("tag", "b"),
+                Param::new("empty", ""),
+                Param {
+                    key_only: true,
+                    ..Param::new("flag", "")
+                },
+                Param::new("name", "Jürgen&co"),
+            ]
+        );
+        assert_eq!(
+            with_params(url, &params),
+            "https://{{host}}/search?q=rust%20egui&tag=a&tag=b&empty=&flag&name=J%C3%BCrgen%26co#top"
+        );
+
+        let mut edited = params.clone();
+        edited[4].value = "on".to_owned();
+        edited.push(Param::new("", "ignored"));
+        edited.push(Param::new("token", "{{token}}"));
+        edited.push(Param {
+            enabled: false,
+            ..Param::new("debug", "1")
+        });
+        assert_eq!(
+            encode(&edited[3..]),
+            "empty=&flag=on&name=J%C3%BCrgen%26co&token={{token}}"
+        );
+        assert_eq!(with_params(url, &[]), "https://{{host}}/search#top");
+        assert_eq!(
+            with_params("/a?", &[Param::new("b", "1 %")]),
+            "/a?b=1%20%25"
+        );
+
+        // Unparsable text survives.
+        assert_eq!(
+            parse("a=%zz&&b=%FF"),
+            vec![Param::new("a", "%zz"), Param::new("b", "%FF")]
+        );
+        assert_eq!(query("no query"), None);
+    }
+
+    #[test]
+    fn test_sync() {
+        let rows = vec![
+            Param {
+                description: "page number".to_owned(),
+                ..Param::new("page", "1")
+            },
+            Param {
+                enabled: false,
+                ..Param::new("debug", "1")
+            },
+            Param {
+                description: "renamed".to_owned(),
+                ..Param::new("sort", "asc")
+            },
+            Param::new("", "no key yet"),
+        ];
+        let synced = sync("/items?page=2&order=desc&limit=10", &rows);
+        let keys: Vec<&str> = synced.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["page", "debug", "order", "", "limit"]);
+        assert_eq!(synced[0].value, "2");
+        assert_eq!(synced[0].description, "page number");
+        assert!(!synced[1].enabled);
+        assert_eq!(synced[2].description, "");
+        assert_eq!(synced[3].value, "no key yet");
+        assert_eq!(
+            with_params("/items", &synced),
+            "/items?page=2&order=desc&limit=10"
+        );
+        assert_eq!(sync("/items?page=2&order=desc&limit=10", &synced), synced);
+    }
+
+    #[test]
+    fn test_legacy_pairs() {
+        let params: Vec<Param> =
+            serde_json::from_str(r#"[["a", "1"], {"key": "b", "key_only": true}]"#).unwrap();
+        assert_eq!(params[0], Param::new("a", "1"));
+        assert!(params[1].key_only);
+        assert!(params[1].enabled);
+    }
+}
@@ -0,0 +1,294 @@
+//! Runs every request of a directory, shown by the Runner window.
+//!
+//! The requests are sent in order through the same path as a tab, so scripts,
+//! assertions, cookies and the history apply as usual and variables set by one
+//! step are seen by the next. With a concurrency above one, several steps run
+//! at the same time. [`Run`] only tracks the steps, the app sends the requests
+//! it hands out by [`Run::due`] and reports back with [`Run::finish`].
+
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RunnerConfig {
+    /// Steps running at the same time, at least one.
+    pub concurrency: usize,
+    /// Milliseconds between starting or finishing a step and starting the next.
+    pub delay: u64,
+    /// Skip the remaining steps after the first failure.
+    pub stop_on_failure: bool,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            delay: 0,
+            stop_on_failure: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepState {
+    Pending,
+    Running,
+    Passed,
+    /// The request failed, or an assertion or script test did not pass.
+    Failed,
+    /// Not sent because the run was stopped.
+    Skipped,
+}
+
+impl StepState {
+    pub fn text(self) -> &'static str {
+        match self {
+            StepState::Pending => "pending",
+            StepState::Running => "running",
+            StepState::Passed => "passed",
+            StepState::Failed => "failed",
+            StepState::Skipped => "skipped",
+        }
+    }
+}
+
+/// A request of the directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The `Location.id` of the request.
+    pub id: String,
+    pub name: String,
+    pub state: StepState,
+    /// `None` if the request failed or was not sent.
+    pub status: Option<usize>,
+    /// Milliseconds until the response or failure.
+    pub elapsed: Option<u128>,
+    /// Why the step failed, e.g. the failed assertions.
+    pub failures: Vec<String>,
+}
+
+/// A run of the requests of a directory.
+#[derive(Clone, Debug)]
+pub struct Run {
+    /// The name of the directory.
+    pub name: String,
+    pub steps: Vec<Step>,
+    config: RunnerConfig,
+    started: bool,
+    /// No step starts before then, see [`RunnerConfig::delay`].
+    next_start: Option<Instant>,
+}
+
+impl Run {
+    /// A run of the `(id, name)` of each request, started by [`Run::start`].
+    pub fn new(name: String, requests: Vec<(String, String)>) -> Self {
+        Self {
+            name,
+            steps: requests
+                .into_iter()
+                .map(|(id, name)| Step {
+                    id,
+                    name,
+                    state: StepState::Pending,
+                    status: None,
+                    elapsed: None,
+                    failures: Vec::new(),
+                })
+                .collect(),
+            config: RunnerConfig::default(),
+            started: false,
+            next_start: None,
+        }
+    }
+
+    /// Start over with `config`, forgetting the results of a previous run.
+    pub fn start(&mut self, config: &RunnerConfig) {
+        for step in &mut self.steps {
+            step.state = StepState::Pending;
+            step.status = None;
+            step.elapsed = None;
+            step.failures.clear();
+        }
+        self.config = config.clone();
+        self.started = true;
+        self.next_start = None;
+    }
+
+    /// Skip the steps not started yet, the running ones still finish.
+    pub fn stop(&mut self) {
+        for step in &mut self.steps {
+            if step.state == StepState::Pending {
+                step.state = StepState::Skipped;
+            }
+        }
+    }
+
+    /// Whether steps are pending or running.
+    pub fn is_running(&self) -> bool {
+        self.started
+            && self
+                .steps
+                .iter()
+                .any(|s| matches!(s.state, StepState::Pending | StepState::Running))
+    }
+
+    /// The ids of the steps to send now, they are marked as running.
+    ///
+    /// Steps start in order. A step whose request is `busy`, e.g. sent from
+    /// its tab, waits until that request finished.
+    pub fn due(&mut self, now: Instant, busy: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut due = Vec::new();
+        if !self.started {
+            return due;
+        }
+        let mut running = self.running().count();
+        while running < self.config.concurrency.max(1)
+            && self.next_start.is_none_or(|next| now >= next)
+        {
+            let Some(step) = self
+                .steps
+                .iter_mut()
+                .find(|s| s.state == StepState::Pending)
+            else {
+                break;
+            };
+            if busy(&step.id) {
+                break;
+            }
+            step.state = StepState::Running;
+            due.push(step.id.clone());
+            running += 1;
+            self.delay(now);
+        }
+        due
+    }
+
+    /// The ids of the running steps.
+    pub fn running(&self) -> impl Iterator<Item = &str> {
+        self.steps
+            .iter()
+            .filter(|s| s.state == StepState::Running)
+            .map(|s| s.id.as_str())
+    }
+
+    /// Record the outcome of the running step `id`, it failed if there are `failures`.
+    pub fn finish(
+        &mut self,
+        id: &str,
+        status: Option<usize>,
+        elapsed: Option<u128>,
+        failures: Vec<String>,
+        now: Instant,
+    ) {
+        let Some(step) = self
+            .steps
+            .iter_mut()
+            .find(|s| s.id == id && s.state == StepState::Running)
+        else {
+            return;
+        };
+        step.state = if failures.is_empty() {
+            StepState::Passed
+        } else {
+            StepState::Failed
+        };
+        step.status = status;
+        step.elapsed = elapsed;
+        step.failures = failures;
+        let failed = step.state == StepState::Failed;
+        self.delay(now);
+        if failed && self.config.stop_on_failure {
+            self.stop();
+        }
+    }
+
+    /// The number of passed and failed steps.
+    pub fn summary(&self) -> (usize, usize) {
+        let count = |state| self.steps.iter().filter(|s| s.state == state).count();
+        (count(StepState::Passed), count(StepState::Failed))
+    }
+
+    fn delay(&mut self, now: Instant) {
+        if self.config.delay > 0 {
+            self.next_start = Some(now + Duration::from_millis(self.config.delay));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> Run {
+        let requests = ["a", "b", "c"]
+            .iter()
+            .map(|id| (id.to_string(), id.to_uppercase()))
+            .collect();
+        Run::new("Dir".to_owned(), requests)
+    }
+
+    #[test]
+    fn test_sequential() {
+        let now = Instant::now();
+        let mut run = run();
+        assert!(run.due(now, |_| false).is_empty());
+
+        run.start(&RunnerConfig::default());
+        assert!(run.due(now, |id| id == "a").is_empty());
+        assert_eq!(run.due(now, |_| false), ["a"]);
+        assert!(run.due(now, |_| false).is_empty());
+
+        run.finish("a", Some(200), Some(12), vec![], now);
+        // Not running anymore.
+        run.finish("a", Some(500), None, vec!["status == 200".to_owned()], now);
+        assert_eq!(run.steps[0].state, StepState::Passed);
+        assert_eq!(run.steps[0].status, Some(200));
+        assert_eq!(run.due(now, |_| false), ["b"]);
+        run.finish(
+            "b",
+            None,
+            Some(3),
+            vec!["connection refused".to_owned()],
+            now,
+        );
+        assert_eq!(run.due(now, |_| false), ["c"]);
+        run.finish("c", Some(200), Some(5), vec![], now);
+        assert!(!run.is_running());
+        assert_eq!(run.summary(), (2, 1));
+    }
+
+    #[test]
+    fn test_concurrency_delay_and_stop() {
+        let now = Instant::now();
+        let mut run = run();
+        run.start(&RunnerConfig {
+            concurrency: 2,
+            delay: 0,
+            stop_on_failure: true,
+        });
+        assert_eq!(run.due(now, |_| false), ["a", "b"]);
+        run.finish(
+            "a",
+            Some(404),
+            Some(1),
+            vec!["status == 200".to_owned()],
+            now,
+        );
+        assert_eq!(run.steps[2].state, StepState::Skipped);
+        assert!(run.is_running());
+        run.finish("b", Some(200), Some(1), vec![], now);
+        assert!(!run.is_running());
+
+        run.start(&RunnerConfig {
+            concurrency: 3,
+            delay: 100,
+            stop_on_failure: false,
+        });
+        assert_eq!(run.steps[2].state, StepState::Pending);
+        assert_eq!(run.due(now, |_| false), ["a"]);
+        assert!(run
+            .due(now + Duration::from_millis(50), |_| false)
+            .is_empty());
+        assert_eq!(run.due(now + Duration::from_millis(100), |_| false), ["b"]);
+    }
+}
@@ -0,0 +1,466 @@
+//! Pre-request and test scripts, written in [Rhai](https://rhai.rs).
+//!
+//! The scripts of a directory run before the ones of its requests. A
+//! pre-request script may change `request` and set variables used to resolve
+//! it, a test script reads `request` and `response`. Scripts have no file or
+//! network access and are stopped after [`MAX_OPERATIONS`].
+//!
+//! `request` has the `method`, `url`, `headers` and `body`, and the text
+//! fields of a form body by name in `form`. Repeated headers and fields are
+//! joined with `, `, the files of a multipart form are not seen. Headers and
+//! fields a script leaves alone are sent as they were, in their order.
+//!
+//! Besides the Rhai standard library, scripts can call:
+//! - `get_variable(name)` and `set_variable(name, value)`, variables set are
+//!   stored in the active environment;
+//! - `test(name, passed)` to log a check;
+//! - `timestamp()`, `timestamp_ms()` and `uuid()`;
+//! - `sha256_hex(text)`, `md5_hex(text)`, `hmac_sha256_hex(key, text)`,
+//!   `base64_encode(text)` and `base64_decode(text)`.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope};
+use sha2::{Digest as _, Sha256};
+
+use crate::environment::VariableScope;
+use crate::executor::Resource;
+use crate::hex;
+
+/// Operations a single run of the scripts may take, so endless loops end.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// The request as seen by scripts, `headers` are the enabled ones.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ScriptRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// The enabled text fields of an url encoded or multipart form body.
+    pub form: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLevel {
+    Info,
+    Error,
+    Passed,
+    Failed,
+}
+
+/// A line of the console.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    pub level: LogLevel,
+    /// The script that logged the line, e.g. `Get user: pre-request`.
+    pub source: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ScriptOutput {
+    /// Set by `set_variable`, in order.
+    pub variables: Vec<(String, String)>,
+    pub log: Vec<LogLine>,
+    /// The error that stopped the scripts.
+    pub error: Option<String>,
+}
+
+impl ScriptOutput {
+    /// Checks logged by `test(name, passed)`, with whether they passed.
+    pub fn tests(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.log.iter().filter_map(|line| match line.level {
+            LogLevel::Passed => Some((line.text.as_str(), true)),
+            LogLevel::Failed => Some((line.text.as_str(), false)),
+            _ => None,
+        })
+    }
+}
+
+/// A script and the source its output is logged with.
+pub type Script = (String, String);
+
+/// Run pre-request `scripts`, which may change `request`.
+///
+/// `variables` are the ones the request is resolved with, `{{variables}}` in
+/// `request` are not resolved yet.
+pub fn pre_request(
+    scripts: &[Script],
+    request: &mut ScriptRequest,
+    variables: &VariableScope,
+) -> ScriptOutput {
+    let mut scope = Scope::new();
+    let original = request_map(request);
+    scope.push("request", original.clone());
+    let output = run(scripts, &mut scope, variables);
+    if let Some(map) = scope.get_value::<Map>("request") {
+        let mut changed = from_request_map(&map);
+        let unchanged = from_request_map(&original);
+        // Keep the rows as they are, e.g. repeated ones apart, unless the script changed them.
+        if changed.headers == unchanged.headers {
+            changed.headers = std::mem::take(&mut request.headers);
+        }
+        if changed.form == unchanged.form {
+            changed.form = std::mem::take(&mut request.form);
+        }
+        *request = changed;
+    }
+    output
+}
+
+/// Run test `scripts` after `response` was received for `request`.
+pub fn tests(
+    scripts: &[Script],
+    request: &ScriptRequest,
+    response: &Resource,
+    variables: &VariableScope,
+) -> ScriptOutput {
+    let mut scope = Scope::new();
+    scope.push_constant("request", request_map(request));
+    scope.push_constant("response", response_map(response));
+    run(scripts, &mut scope, variables)
+}
+
+#[derive(Default)]
+struct State {
+    source: String,
+    variables: VariableScope,
+    output: ScriptOutput,
+}
+
+impl State {
+    fn log(&mut self, level: LogLevel, text: impl Into<String>) {
+        self.output.log.push(LogLine {
+            level,
+            source: self.source.clone(),
+            text: text.into(),
+        });
+    }
+}
+
+fn run(scripts: &[Script], scope: &mut Scope, variables: &VariableScope) -> ScriptOutput {
+    let state = Rc::new(RefCell::new(State {
+        variables: variables.clone(),
+        ..Default::default()
+    }));
+    let engine = engine(&state);
+    for (source, code) in scripts {
+        state.borrow_mut().source = source.clone();
+        if let Err(err) = engine.run_with_scope(scope, code) {
+            let mut state = state.borrow_mut();
+            state.log(LogLevel::Error, err.to_string());
+            state.output.error = Some(format!("{source}: {err}"));
+            break;
+        }
+    }
+    let output = std::mem::take(&mut state.borrow_mut().output);
+    output
+}
+
+/// A sandboxed engine logging to `state`.
+fn engine(state: &Rc<RefCell<State>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(64);
+    engine.set_max_string_size(16 * 1024 * 1024);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(100_000);
+
+    let log = state.clone();
+    engine.on_print(move |text| log.borrow_mut().log(LogLevel::Info, text));
+    let log = state.clone();
+    engine.on_debug(move |text, _, position| {
+        log.borrow_mut()
+            .log(LogLevel::Info, format!("{text} ({position})"))
+    });
+    let log = state.clone();
+    engine.register_fn("test", move |name: &str, passed: bool| {
+        let level = if passed {
+            LogLevel::Passed
+        } else {
+            LogLevel::Failed
+        };
+        log.borrow_mut().log(level, name);
+    });
+
+    let variables = state.clone();
+    engine.register_fn("get_variable", move |name: &str| -> Dynamic {
+        let state = variables.borrow();
+        let set = state
+            .output
+            .variables
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name);
+        match set
+            .map(|(_, value)| value.as_str())
+            .or_else(|| state.variables.get(name))
+        {
+            Some(value) => value.into(),
+            None => Dynamic::UNIT,
+        }
+    });
+    let variables = state.clone();
+    engine.register_fn("set_variable", move |name: &str, value: Dynamic| {
+        let value = value.to_string();
+        variables
+            .borrow_mut()
+            .output
+            .variables
+            .push((name.to_owned(), value));
+    });
+
+    engine.register_fn("timestamp", || unix_time().as_secs() as i64);
+    engine.register_fn("timestamp_ms", || unix_time().as_millis() as i64);
+    engine.register_fn("uuid", || uuid::Uuid::new_v4().to_string());
+    engine.register_fn("sha256_hex", |text: &str| {
+        hex::encode(&Sha256::digest(text))
+    });
+    engine.register_fn("md5_hex", |text: &str| hex::encode(&Md5::digest(text)));
+    engine.register_fn("hmac_sha256_hex", |key: &str, text: &str| {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any size");
+        mac.update(text.as_bytes());
+        hex::encode(&mac.finalize().into_bytes())
+    });
+    engine.register_fn("base64_encode", |text: &str| {
+        base64::engine::general_purpose::STANDARD.encode(text)
+    });
+    engine.register_fn(
+        "base64_decode",
+        |text: &str| -> Result<String, Box<EvalAltResult>> {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(|err| err.to_string())?;
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        },
+    );
+    engine
+}
+
+fn unix_time() -> std::time::Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// `headers` by name, repeated headers are joined with `, `.
+fn header_map(headers: &[(String, String)], lower_case: bool) -> Map {
+    let mut map = Map::new();
+    for (name, value) in headers {
+        let name = if lower_case {
+            name.to_lowercase()
+        } else {
+            name.clone()
+        };
+        match map.get_mut(name.as_str()) {
+            Some(joined) => *joined = format!("{joined}, {value}").into(),
+            None => {
+                map.insert(name.into(), value.clone().into());
+            }
+        }
+    }
+    map
+}
+
+fn request_map(request: &ScriptRequest) -> Map {
+    let mut map = Map::new();
+    map.insert("method".into(), request.method.clone().into());
+    map.insert("url".into(), request.url.clone().into());
+    map.insert("headers".into(), header_map(&request.headers, false).into());
+    map.insert("body".into(), request.body.clone().into());
+    map.insert("form".into(), header_map(&request.form, false).into());
+    map
+}
+
+fn from_request_map(map: &Map) -> ScriptRequest {
+    let text = |key: &str| map.get(key).map(Dynamic::to_string).unwrap_or_default();
+    let pairs = |key: &str| {
+        map.get(key)
+            .and_then(|pairs| pairs.read_lock::<Map>())
+            .map(|pairs| {
+                pairs
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    ScriptRequest {
+        method: text("method"),
+        url: text("url"),
+        headers: pairs("headers"),
+        body: text("body"),
+        form: pairs("form"),
+    }
+}
+
+/// `status`, `status_text`, `headers` by lower case name, `body`, `elapsed` in
+/// milliseconds and the body parsed as `json`, `()` if it is not JSON.
+fn response_map(response: &Resource) -> Map {
+    let json = serde_json::from_str::<serde_json::Value>(&response.body)
+        .ok()
+        .and_then(|json| rhai::serde::to_dynamic(json).ok())
+        .unwrap_or(Dynamic::UNIT);
+    let mut map = Map::new();
+    map.insert("status".into(), (response.status as i64).into());
+    map.insert("status_text".into(), response.status_text.clone().into());
+    map.insert("headers".into(), header_map(&response.headers, true).into());
+    map.insert("body".into(), response.body.clone().into());
+    map.insert("elapsed".into(), (response.elapsed as i64).into());
+    map.insert("json".into(), json);
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(code: &str) -> Vec<Script> {
+        vec![("test".to_owned(), code.to_owned())]
+    }
+
+    #[test]
+    fn test_pre_request() {
+        let mut variables = VariableScope::default();
+        variables.push_layer(&[("secret".to_owned(), "key".to_owned())]);
+        let mut request = ScriptRequest {
+            method: "GET".to_owned(),
+            url: "https://{{host}}/items".to_owned(),
+            headers: vec![("Accept".to_owned(), "*/*".to_owned())],
+            body: String::new(),
+            form: vec![("tag".to_owned(), "a".to_owned())],
+        };
+        let scripts = vec![
+            (
+                "directory".to_owned(),
+                r#"set_variable("host", "example.org");"#.to_owned(),
+            ),
+            (
+                "request".to_owned(),
+                r#"
+                let signature = hmac_sha256_hex(get_variable("secret"), "The quick brown fox jumps over the lazy dog");
+                request.headers["X-Signature"] = signature;
+                request.url += "?host=" + get_variable("host");
+                request.method = "POST";
+                request.form.nonce = "n1";
+                print(`signed ${request.url}`);
+                if get_variable("missing") == () { set_variable("count", 1 + 1); }
+                "#
+                .to_owned(),
+            ),
+        ];
+        let output = pre_request(&scripts, &mut request, &variables);
+        assert_eq!(output.error, None);
+        assert_eq!(
+            output.variables,
+            vec![
+                ("host".to_owned(), "example.org".to_owned()),
+                ("count".to_owned(), "2".to_owned())
+            ]
+        );
+        assert_eq!(output.log.len(), 1);
+        assert_eq!(output.log[0].source, "request");
+        assert_eq!(
+            output.log[0].text,
+            "signed https://{{host}}/items?host=example.org"
+        );
+        assert_eq!(request.method, "POST");
+        assert_eq!(
+            request.headers[1],
+            (
+                "X-Signature".to_owned(),
+                "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8".to_owned()
+            )
+        );
+        assert_eq!(
+            request.form,
+            vec![
+                ("nonce".to_owned(), "n1".to_owned()),
+                ("tag".to_owned(), "a".to_owned())
+            ]
+        );
+
+        // Repeated headers and fields stay apart, in order, if they are not changed.
+        let headers = vec![
+            ("X-Trace".to_owned(), "1".to_owned()),
+            ("Cookie".to_owned(), "a=1".to_owned()),
+            ("Cookie".to_owned(), "b=2".to_owned()),
+        ];
+        let form = vec![
+            ("tag".to_owned(), "a".to_owned()),
+            ("tag".to_owned(), "b".to_owned()),
+        ];
+        let mut request = ScriptRequest {
+            headers: headers.clone(),
+            form: form.clone(),
+            ..Default::default()
+        };
+        let output = pre_request(
+            &script(r#"if request.form.tag == "a, b" { request.body = "seen"; }"#),
+            &mut request,
+            &variables,
+        );
+        assert_eq!(output.error, None);
+        assert_eq!(request.body, "seen");
+        assert_eq!(request.headers, headers);
+        assert_eq!(request.form, form);
+    }
+
+    #[test]
+    fn test_tests() {
+        let response = Resource {
+            status: 201,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: r#"{"data": {"id": 7, "tags": ["a"]}}"#.to_owned(),
+            ..Default::default()
+        };
+        let output = tests(
+            &script(
+                r#"
+                test("created", response.status == 201);
+                test("json", response.headers["content-type"].contains("json"));
+                test("tag", response.json.data.tags[0] == "b");
+                set_variable("id", response.json.data.id);
+                "#,
+            ),
+            &ScriptRequest::default(),
+            &response,
+            &VariableScope::default(),
+        );
+        assert_eq!(output.error, None);
+        let tests: Vec<(&str, bool)> = output.tests().collect();
+        assert_eq!(tests, [("created", true), ("json", true), ("tag", false)]);
+        assert_eq!(output.variables, vec![("id".to_owned(), "7".to_owned())]);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let output = tests(
+            &script("loop {}"),
+            &ScriptRequest::default(),
+            &Resource::default(),
+            &VariableScope::default(),
+        );
+        assert!(output.error.unwrap().starts_with("test: "));
+        assert_eq!(output.log[0].level, LogLevel::Error);
+
+        let scripts = vec![
+            (
+                "first".to_owned(),
+                "request.body = base64_decode(\"!\");".to_owned(),
+            ),
+            ("second".to_owned(), "print(\"not run\");".to_owned()),
+        ];
+        let mut request = ScriptRequest::default();
+        let output = pre_request(&scripts, &mut request, &VariableScope::default());
+        assert!(output.error.unwrap().starts_with("first: "));
+        assert_eq!(output.log.len(), 1);
+    }
+}
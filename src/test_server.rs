@@ -0,0 +1,123 @@
+//! A tiny stand-in HTTP server for tests.
+//!
+//! Every connection is handled by `handler`, which gets the parsed request
+//! and returns the raw bytes to write back.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, Clone, Default)]
+pub struct TestRequest {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl TestRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Serve connections on a random local port until the test process exits.
+///
+/// Returns the base url, e.g. `http://127.0.0.1:4242`.
+pub fn serve<F>(handler: F) -> String
+where
+    F: Fn(&TestRequest) -> Vec<u8> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = handler.clone();
+            thread::spawn(move || handle(stream, handler.as_ref()));
+        }
+    });
+    format!("http://{addr}")
+}
+
+/// Like [`serve`], over TLS with the given server config.
+///
+/// Returns the base url, e.g. `https://localhost:4242`.
+pub fn serve_tls<F>(config: Arc<rustls::ServerConfig>, handler: F) -> String
+where
+    F: Fn(&TestRequest) -> Vec<u8> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handler = Arc::new(handler);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = handler.clone();
+            let config = config.clone();
+            thread::spawn(move || {
+                let Ok(connection) = rustls::ServerConnection::new(config) else {
+                    return;
+                };
+                let mut stream = rustls::StreamOwned::new(connection, stream);
+                handle(&mut stream, handler.as_ref());
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            });
+        }
+    });
+    format!("https://localhost:{port}")
+}
+
+fn handle(stream: impl Read + Write, handler: &dyn Fn(&TestRequest) -> Vec<u8>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let mut request = TestRequest {
+        method: parts.next().unwrap_or_default().to_owned(),
+        target: parts.next().unwrap_or_default().to_owned(),
+        ..Default::default()
+    };
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            request
+                .headers
+                .push((k.trim().to_owned(), v.trim().to_owned()));
+        }
+    }
+    let length: usize = request
+        .header("Content-Length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    request.body = vec![0; length];
+    if reader.read_exact(&mut request.body).is_err() {
+        return;
+    }
+    let _ = reader.into_inner().write_all(&handler(&request));
+}
+
+/// A complete response with the given status line, extra headers and body.
+pub fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n", body.len());
+    for (k, v) in headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str("Connection: close\r\n\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(body);
+    out
+}
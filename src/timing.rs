@@ -0,0 +1,146 @@
+//! Timing of the phases of a request.
+//!
+//! ureq has no timing hooks, so the phases are measured through the DNS
+//! resolver and TLS connector installed on the agent.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ureq::Resolver;
+
+/// How long each phase of the final request of a redirect chain took.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Timings {
+    /// Earlier requests of the redirect chain.
+    pub redirects: Duration,
+    /// Zero when a pooled connection was reused.
+    pub dns: Duration,
+    /// TCP connect, only known for HTTPS where the TLS handshake marks its end.
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    /// Time to first byte, from connected until the response headers arrived.
+    ///
+    /// Includes the TCP connect for plain HTTP.
+    pub wait: Duration,
+    pub download: Duration,
+}
+
+impl Timings {
+    /// The phases in order with their names, unknown phases are skipped.
+    pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+        [
+            ("redirects", Some(self.redirects).filter(|d| !d.is_zero())),
+            ("dns", Some(self.dns)),
+            ("connect", self.connect),
+            ("tls", self.tls),
+            ("wait", Some(self.wait)),
+            ("download", Some(self.download)),
+        ]
+        .into_iter()
+        .filter_map(|(name, duration)| Some((name, duration?)))
+        .collect()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, d)| *d).sum()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Marks {
+    dns: Option<(Instant, Instant)>,
+    tls: Option<(Instant, Instant)>,
+}
+
+/// Collects the marks of the hooks of one agent.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder(Arc<Mutex<Marks>>);
+
+impl Recorder {
+    /// Forget the marks of the previous request of a redirect chain.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = Marks::default();
+    }
+
+    /// Record a TLS handshake, see [`crate::tls::Connector`].
+    pub fn record_tls(&self, start: Instant, end: Instant) {
+        self.0.lock().unwrap().tls = Some((start, end));
+    }
+
+    /// Split the last request into phases.
+    ///
+    /// `start` is when the redirect chain started, `sent` when the last request
+    /// was sent, `headers` when its headers arrived and `done` when its body was read.
+    pub fn timings(
+        &self,
+        start: Instant,
+        sent: Instant,
+        headers: Instant,
+        done: Instant,
+    ) -> Timings {
+        let marks = self.0.lock().unwrap();
+        let mut timings = Timings {
+            redirects: sent - start,
+            download: done - headers,
+            ..Default::default()
+        };
+        let mut connected = sent;
+        if let Some((dns_start, dns_end)) = marks.dns {
+            timings.dns = dns_end - dns_start;
+            connected = dns_end;
+        }
+        if let Some((tls_start, tls_end)) = marks.tls {
+            timings.connect = Some(tls_start.saturating_duration_since(connected));
+            timings.tls = Some(tls_end - tls_start);
+            connected = tls_end;
+        }
+        timings.wait = headers.saturating_duration_since(connected);
+        timings
+    }
+}
+
+/// Resolves with the standard library, recording the lookup time.
+pub struct TimingResolver(pub Recorder);
+
+impl Resolver for TimingResolver {
+    fn resolve(&self, netloc: &str) -> std::io::Result<Vec<SocketAddr>> {
+        let start = Instant::now();
+        let addrs = netloc.to_socket_addrs().map(|addrs| addrs.collect());
+        self.0 .0.lock().unwrap().dns = Some((start, Instant::now()));
+        addrs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let sent = start + ms(100);
+        let recorder = Recorder::default();
+        recorder.0.lock().unwrap().dns = Some((sent + ms(1), sent + ms(11)));
+        recorder.0.lock().unwrap().tls = Some((sent + ms(31), sent + ms(61)));
+
+        let timings = recorder.timings(start, sent, sent + ms(161), sent + ms(171));
+        assert_eq!(timings.redirects, ms(100));
+        assert_eq!(timings.dns, ms(10));
+        assert_eq!(timings.connect, Some(ms(20)));
+        assert_eq!(timings.tls, Some(ms(30)));
+        assert_eq!(timings.wait, ms(100));
+        assert_eq!(timings.download, ms(10));
+        assert_eq!(timings.total(), ms(270));
+
+        // Plain HTTP: the connect is part of the wait.
+        recorder.reset();
+        recorder.0.lock().unwrap().dns = Some((start, start + ms(5)));
+        let timings = recorder.timings(start, start, start + ms(50), start + ms(60));
+        let names: Vec<&str> = timings.phases().iter().map(|p| p.0).collect();
+        assert_eq!(names, vec!["dns", "wait", "download"]);
+        assert_eq!(timings.wait, ms(45));
+    }
+}
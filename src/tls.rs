@@ -0,0 +1,435 @@
+//! TLS configuration: extra trusted CAs, client certificates and skipping
+//! verification, and what was negotiated with the server.
+//!
+//! [`TlsSettings`] can be set globally and on the collection, they are
+//! combined into the [`TlsOptions`] of one request.
+
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned};
+use ureq::{ReadWrite, TlsConnector};
+
+use crate::timing::Recorder;
+
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// PEM files with CA certificates trusted besides the webpki roots.
+    pub ca_files: Vec<String>,
+    /// Accept any server certificate, for any host.
+    pub insecure: bool,
+    pub client_certs: Vec<ClientCert>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum CertFormat {
+    /// Certificate chain and private key in PEM files.
+    #[default]
+    Pem,
+    /// Certificate chain and private key in a password protected `.p12`/`.pfx` file.
+    Pkcs12,
+}
+
+impl CertFormat {
+    pub fn text(self) -> &'static str {
+        match self {
+            CertFormat::Pem => "PEM",
+            CertFormat::Pkcs12 => "PKCS#12",
+        }
+    }
+}
+
+/// A client certificate presented to the hosts matching `host`.
+#[derive(Clone, Debug, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ClientCert {
+    /// `api.example.org`, `*.example.org` for its subdomains or empty for every host.
+    pub host: String,
+    pub format: CertFormat,
+    /// The certificate chain, or the PKCS#12 file.
+    pub cert_file: String,
+    /// The private key, empty when it is in `cert_file`. Unused for PKCS#12.
+    pub key_file: String,
+    /// Password of the PKCS#12 file.
+    pub password: String,
+}
+
+impl ClientCert {
+    pub fn matches(&self, host: &str) -> bool {
+        let pattern = self.host.trim().to_lowercase();
+        let host = host.trim_matches(['[', ']']).to_lowercase();
+        match pattern.strip_prefix("*.") {
+            _ if pattern.is_empty() => true,
+            Some(domain) => host.ends_with(&format!(".{domain}")),
+            None => host == pattern,
+        }
+    }
+
+    /// Read the certificate chain and private key.
+    fn load(&self) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), String> {
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map_err(|err| format!("cannot read client certificate {path}: {err}"))
+        };
+        match self.format {
+            CertFormat::Pem => {
+                let certs = pem_certificates(&self.cert_file)?;
+                let key_file = match self.key_file.trim() {
+                    "" => &self.cert_file,
+                    key_file => key_file,
+                };
+                let key = rustls_pemfile::private_key(&mut BufReader::new(&read(key_file)?[..]))
+                    .map_err(|err| format!("invalid private key in {key_file}: {err}"))?
+                    .ok_or_else(|| format!("no private key in {key_file}"))?;
+                Ok((certs, key))
+            }
+            CertFormat::Pkcs12 => {
+                let keystore =
+                    p12_keystore::KeyStore::from_pkcs12(&read(&self.cert_file)?, &self.password)
+                        .map_err(|err| format!("cannot open {}: {err}", self.cert_file))?;
+                let (_, chain) = keystore
+                    .private_key_chain()
+                    .ok_or_else(|| format!("no private key in {}", self.cert_file))?;
+                let certs = chain
+                    .chain()
+                    .iter()
+                    .map(|cert| CertificateDer::from(cert.as_der().to_vec()))
+                    .collect();
+                let key = PrivatePkcs8KeyDer::from(chain.key().to_vec());
+                Ok((certs, key.into()))
+            }
+        }
+    }
+}
+
+/// The TLS configuration of a single request.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TlsOptions {
+    pub ca_files: Vec<String>,
+    pub client_cert: Option<ClientCert>,
+    pub insecure: bool,
+}
+
+impl TlsOptions {
+    /// Combine `settings`, from the most to the least specific, for a request to `url`.
+    ///
+    /// CA files add up, the first matching client certificate is used.
+    pub fn resolve<'a>(url: &str, settings: impl IntoIterator<Item = &'a TlsSettings>) -> Self {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        let mut options = TlsOptions::default();
+        for settings in settings {
+            options.insecure |= settings.insecure;
+            options.ca_files.extend(
+                settings
+                    .ca_files
+                    .iter()
+                    .filter(|file| !file.trim().is_empty())
+                    .cloned(),
+            );
+            if options.client_cert.is_none() {
+                options.client_cert = settings
+                    .client_certs
+                    .iter()
+                    .find(|cert| !cert.cert_file.trim().is_empty() && cert.matches(&host))
+                    .cloned();
+            }
+        }
+        options
+    }
+
+    /// The rustls configuration for these options.
+    ///
+    /// Errors describe a CA or client certificate file that cannot be used.
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, String> {
+        if *self == TlsOptions::default() {
+            return Ok(default_tls_config());
+        }
+        let builder = if self.insecure {
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(SkipVerification(
+                    rustls::crypto::ring::default_provider().signature_verification_algorithms,
+                )))
+        } else {
+            let mut roots = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            for file in &self.ca_files {
+                for cert in pem_certificates(file)? {
+                    roots
+                        .add(cert)
+                        .map_err(|err| format!("invalid CA certificate in {file}: {err}"))?;
+                }
+            }
+            ClientConfig::builder().with_root_certificates(roots)
+        };
+        let config = match &self.client_cert {
+            Some(cert) => {
+                let (chain, key) = cert.load()?;
+                builder.with_client_auth_cert(chain, key).map_err(|err| {
+                    format!("invalid client certificate {}: {err}", cert.cert_file)
+                })?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+}
+
+fn pem_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = std::fs::File::open(path).map_err(|err| format!("cannot read {path}: {err}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid PEM in {path}: {err}"))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {path}"));
+    }
+    Ok(certs)
+}
+
+/// The rustls configuration ureq uses by default, trusting the webpki roots.
+pub fn default_tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            Arc::new(
+                ClientConfig::builder()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Accepts every server certificate, but still checks the handshake signatures.
+#[derive(Debug)]
+struct SkipVerification(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// What was negotiated with the server.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TlsInfo {
+    /// e.g. `TLSv1.3`.
+    pub version: String,
+    pub cipher_suite: String,
+    /// The chain sent by the server, starting with its own certificate.
+    pub peer_certificates: Vec<CertificateInfo>,
+    /// The server certificate was not verified.
+    pub insecure: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+}
+
+impl CertificateInfo {
+    fn parse(der: &[u8]) -> Self {
+        match x509_parser::parse_x509_certificate(der) {
+            Ok((_, cert)) => Self {
+                subject: cert.subject().to_string(),
+                issuer: cert.issuer().to_string(),
+                serial: cert.raw_serial_as_string(),
+                not_before: cert.validity().not_before.to_string(),
+                not_after: cert.validity().not_after.to_string(),
+            },
+            Err(err) => Self {
+                subject: format!("unparsable certificate: {err}"),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn tls_info(connection: &ClientConnection, insecure: bool) -> TlsInfo {
+    let version = match connection.protocol_version() {
+        Some(rustls::ProtocolVersion::TLSv1_3) => "TLSv1.3".to_owned(),
+        Some(rustls::ProtocolVersion::TLSv1_2) => "TLSv1.2".to_owned(),
+        Some(version) => format!("{version:?}"),
+        None => String::new(),
+    };
+    let cipher_suite = connection
+        .negotiated_cipher_suite()
+        .map(|suite| format!("{:?}", suite.suite()))
+        .unwrap_or_default();
+    let peer_certificates = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert| CertificateInfo::parse(cert))
+        .collect();
+    TlsInfo {
+        version,
+        cipher_suite,
+        peer_certificates,
+        insecure,
+    }
+}
+
+/// Connects with rustls, recording the handshake time and what was negotiated.
+pub struct Connector {
+    pub config: Arc<ClientConfig>,
+    pub insecure: bool,
+    pub recorder: Recorder,
+    /// The last handshake of the agent.
+    pub info: Arc<Mutex<Option<TlsInfo>>>,
+}
+
+impl TlsConnector for Connector {
+    fn connect(
+        &self,
+        dns_name: &str,
+        mut io: Box<dyn ReadWrite>,
+    ) -> Result<Box<dyn ReadWrite>, ureq::Error> {
+        let start = Instant::now();
+        // rustls does not accept the brackets of IPv6 addresses.
+        let dns_name = dns_name.trim_start_matches('[').trim_end_matches(']');
+        // ureq errors can only be created from io errors, a wrapped
+        // `rustls::Error` classifies them as TLS failures.
+        let name = ServerName::try_from(dns_name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+            .to_owned();
+        let mut connection =
+            ClientConnection::new(self.config.clone(), name).map_err(io::Error::other)?;
+        let handshake = connection.complete_io(&mut io);
+        self.recorder.record_tls(start, Instant::now());
+        handshake?;
+        *self.info.lock().unwrap() = Some(tls_info(&connection, self.insecure));
+        Ok(Box::new(TlsStream(StreamOwned::new(connection, io))))
+    }
+}
+
+struct TlsStream(StreamOwned<ClientConnection, Box<dyn ReadWrite>>);
+
+impl ReadWrite for TlsStream {
+    fn socket(&self) -> Option<&TcpStream> {
+        self.0.get_ref().socket()
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(err) if is_close_notify(&err) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TlsStream").finish()
+    }
+}
+
+/// A closed TLS session is the end of the body, like in ureq.
+fn is_close_notify(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::ConnectionAborted
+        && err
+            .get_ref()
+            .is_some_and(|inner| inner.to_string().contains("CloseNotify"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_options() {
+        let cert = |host: &str, file: &str| ClientCert {
+            host: host.to_owned(),
+            cert_file: file.to_owned(),
+            ..Default::default()
+        };
+        let global = TlsSettings {
+            ca_files: vec!["global.pem".to_owned()],
+            client_certs: vec![cert("", "any.pem")],
+            ..Default::default()
+        };
+        let collection = TlsSettings {
+            ca_files: vec!["corp.pem".to_owned(), " ".to_owned()],
+            insecure: true,
+            client_certs: vec![cert("*.corp.example", "corp.pem"), cert("db", "")],
+        };
+
+        let options = TlsOptions::resolve("https://api.corp.example/x", [&collection, &global]);
+        assert_eq!(options.ca_files, vec!["corp.pem", "global.pem"]);
+        assert!(options.insecure);
+        assert_eq!(options.client_cert.unwrap().cert_file, "corp.pem");
+
+        let options = TlsOptions::resolve("https://corp.example/", [&collection, &global]);
+        assert_eq!(options.client_cert.unwrap().cert_file, "any.pem");
+        let options = TlsOptions::resolve("https://db/", [&collection]);
+        assert_eq!(options.client_cert, None);
+
+        assert!(TlsOptions::resolve("http://x/", []).client_config().is_ok());
+        let missing = TlsOptions {
+            ca_files: vec!["/nonexistent/ca.pem".to_owned()],
+            ..Default::default()
+        };
+        assert!(missing.client_config().unwrap_err().contains("ca.pem"));
+    }
+}
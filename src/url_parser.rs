@@ -24,25 +24,33 @@ pub struct URI<'a> {
 
 impl URI<'_> {
     pub fn to_string(&mut self) -> String {
+        // Explicit `as_str()`, rhai makes `String + &String` ambiguous.
         return self.scheme.to_string()
-            + &self.host.to_string()
-            + &self
+            + self.host.to_string().as_str()
+            + self
                 .port
-                .map_or_else(|| "".to_string(), |v| ":".to_string() + &v.to_string())
-            + &self
+                .map_or_else(|| "".to_string(), |v| ":".to_string() + v.to_string().as_str())
+                .as_str()
+            + self
                 .path
                 .as_ref()
                 .map_or_else(|| "".to_string(), |v| "/".to_string() + v.join("/").as_str())
-            + &self.query.clone().map_or_else(
-                || "".to_string(),
-                |v| {
-                    "?".to_string()
-                        + &v.iter()
-                            .map(|f| f.0.to_string() + "=" + f.1)
-                            .collect::<Vec<_>>()
-                            .join("&")
-                },
-            );
+                .as_str()
+            + self
+                .query
+                .clone()
+                .map_or_else(
+                    || "".to_string(),
+                    |v| {
+                        "?".to_string()
+                            + v.iter()
+                                .map(|f| f.0.to_string() + "=" + f.1)
+                                .collect::<Vec<_>>()
+                                .join("&")
+                                .as_str()
+                    },
+                )
+                .as_str();
     }
 }
 